@@ -0,0 +1,326 @@
+use crate::*;
+use nalgebra::{Matrix4, Quaternion, UnitQuaternion, Vector3};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    CubicSpline,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Path {
+    Translation,
+    Rotation,
+    Scale,
+    Weights,
+}
+
+// the values are flat, as in the file. rotations are [x, y, z, w]. with cubic splines every key holds an in-tangent,
+// the value and an out-tangent.
+#[derive(Clone, Debug)]
+pub struct Channel {
+    pub node: usize,
+    pub path: Path,
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>,
+    pub values: Vec<f32>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Clip {
+    pub name: String,
+    pub channels: Vec<Channel>,
+    // VRM Animation. the expression weights are scalars, the lookAt rotations turn +z to where the eyes look, seen
+    // from the head.
+    pub expressions: Vec<(String, Channel)>,
+    pub look_at: Option<Channel>,
+}
+
+// the local transforms of all the nodes.
+#[derive(Clone, Debug)]
+pub struct Pose {
+    pub translations: Vec<Vector3<f32>>,
+    pub rotations: Vec<UnitQuaternion<f32>>,
+    pub scales: Vec<Vector3<f32>>,
+}
+
+// plays one of the clips of a scene in a loop.
+pub struct Player {
+    pub clip: usize,
+    pub time: f32,
+    pub playing: bool,
+}
+
+fn quaternion(v: &[f32]) -> UnitQuaternion<f32> {
+    UnitQuaternion::from_quaternion(Quaternion::new(v[3], v[0], v[1], v[2]))
+}
+
+impl Channel {
+    // the number of components of a value.
+    fn width(&self) -> usize {
+        let keys = match self.interpolation {
+            Interpolation::CubicSpline => self.times.len() * 3,
+            _ => self.times.len(),
+        };
+        self.values.len() / keys.max(1)
+    }
+
+    // k is 0 for the in-tangent, 1 for the value and 2 for the out-tangent.
+    fn key(&self, i: usize, k: usize) -> &[f32] {
+        let n = self.width();
+        match self.interpolation {
+            Interpolation::CubicSpline => &self.values[(i * 3 + k) * n..(i * 3 + k + 1) * n],
+            _ => &self.values[i * n..(i + 1) * n],
+        }
+    }
+
+    pub fn sample(&self, time: f32) -> Vec<f32> {
+        let i = self.times.partition_point(|t| *t <= time);
+        if i == 0 {
+            return self.key(0, 1).to_vec();
+        }
+        if i == self.times.len() {
+            return self.key(i - 1, 1).to_vec();
+        }
+        let dt = self.times[i] - self.times[i - 1];
+        let s = (time - self.times[i - 1]) / dt;
+        let (a, b) = (self.key(i - 1, 1), self.key(i, 1));
+        match (self.interpolation, self.path) {
+            (Interpolation::Step, _) => a.to_vec(),
+            (Interpolation::Linear, Path::Rotation) => {
+                let q = quaternion(a).slerp(&quaternion(b), s);
+                q.coords.iter().copied().collect()
+            }
+            (Interpolation::Linear, _) => a.iter().zip(b).map(|(a, b)| a + (b - a) * s).collect(),
+            (Interpolation::CubicSpline, _) => {
+                let (s2, s3) = (s * s, s * s * s);
+                let out = self.key(i - 1, 2);
+                let r#in = self.key(i, 0);
+                let v = (0..a.len()).map(|j| {
+                    (2.0 * s3 - 3.0 * s2 + 1.0) * a[j]
+                        + (s3 - 2.0 * s2 + s) * dt * out[j]
+                        + (-2.0 * s3 + 3.0 * s2) * b[j]
+                        + (s3 - s2) * dt * r#in[j]
+                });
+                let v = v.collect::<Vec<_>>();
+                match self.path {
+                    Path::Rotation => quaternion(&v).coords.iter().copied().collect(),
+                    _ => v,
+                }
+            }
+        }
+    }
+}
+
+impl Clip {
+    fn all_channels(&self) -> impl Iterator<Item = &Channel> {
+        let expressions = self.expressions.iter().map(|(_, c)| c);
+        self.channels.iter().chain(expressions).chain(self.look_at.iter())
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.all_channels()
+            .filter_map(|c| c.times.last().copied())
+            .fold(0.0, f32::max)
+    }
+
+    // the times of all the keys, sorted and without duplicates.
+    pub fn times(&self) -> Vec<f32> {
+        let mut times = self
+            .all_channels()
+            .flat_map(|c| c.times.iter().copied())
+            .collect::<Vec<_>>();
+        times.sort_by(f32::total_cmp);
+        times.dedup_by(|a, b| *a - *b < 1e-5);
+        times
+    }
+
+    // writes the transforms at "time" into the pose.
+    pub fn pose(&self, pose: &mut Pose, time: f32) {
+        for channel in self.channels.iter() {
+            if channel.times.is_empty() || channel.node >= pose.rotations.len() {
+                continue;
+            }
+            let v = channel.sample(time);
+            match channel.path {
+                Path::Translation => pose.translations[channel.node] = Vector3::from_column_slice(&v),
+                Path::Rotation => pose.rotations[channel.node] = quaternion(&v),
+                Path::Scale => pose.scales[channel.node] = Vector3::from_column_slice(&v),
+                Path::Weights => (),
+            }
+        }
+    }
+
+    // the morph weights at "time", by the mesh.
+    pub fn weights(&self, nodes: &[scene::Node], time: f32) -> Vec<(usize, Vec<f32>)> {
+        self.channels
+            .iter()
+            .filter(|c| c.path == Path::Weights && !c.times.is_empty())
+            .filter_map(|c| match nodes.get(c.node)?.element {
+                scene::Element::Mesh(mesh) => Some((mesh, c.sample(time))),
+                scene::Element::None => None,
+            })
+            .collect()
+    }
+
+    pub fn expression_weights(&self, time: f32) -> Vec<(String, f32)> {
+        self.expressions
+            .iter()
+            .filter(|(_, c)| !c.times.is_empty())
+            .map(|(name, c)| (name.clone(), c.sample(time)[0]))
+            .collect()
+    }
+
+    // the yaw and the pitch in degrees, as look_at::Controller::angles().
+    pub fn look_at_angles(&self, time: f32) -> Option<(f32, f32)> {
+        let channel = self.look_at.as_ref().filter(|c| !c.times.is_empty())?;
+        let d = quaternion(&channel.sample(time)) * Vector3::z();
+        let yaw = f32::atan2(d[0], d[2]).to_degrees();
+        let pitch = f32::atan2(d[1], f32::hypot(d[0], d[2])).to_degrees();
+        Some((yaw, pitch))
+    }
+}
+
+impl Pose {
+    pub fn new(glb: &scene::Glb) -> Self {
+        Pose {
+            translations: glb.nodes.iter().map(|n| n.translation).collect(),
+            rotations: glb.nodes.iter().map(|n| n.rotation).collect(),
+            scales: glb.nodes.iter().map(|n| n.scale).collect(),
+        }
+    }
+
+    pub fn apply(&self, glb: &mut scene::Glb) {
+        for (i, node) in glb.nodes.iter_mut().enumerate() {
+            node.translation = self.translations[i];
+            node.rotation = self.rotations[i];
+            node.scale = self.scales[i];
+        }
+    }
+
+    pub fn transform(&self, node: usize) -> Matrix4<f32> {
+        let mt = Matrix4::new_translation(&self.translations[node]);
+        let mr = self.rotations[node].to_homogeneous();
+        let ms = Matrix4::new_nonuniform_scaling(&self.scales[node]);
+        mt * mr * ms
+    }
+
+    // the transforms relative to the scene, with the hierarchy of "glb".
+    pub fn world_transforms(&self, glb: &scene::Glb) -> Vec<Matrix4<f32>> {
+        let locals = (0..self.rotations.len()).map(|n| self.transform(n)).collect::<Vec<_>>();
+        glb.compose(&locals)
+    }
+}
+
+impl Player {
+    pub fn new(clip: usize) -> Self {
+        Player {
+            clip: clip,
+            time: 0.0,
+            playing: true,
+        }
+    }
+
+    // advances the time and poses the scene. the nodes without a channel are put back into the rest pose. returns the
+    // materials to be uploaded again.
+    pub fn update(&mut self, glb: &mut scene::Glb, rest: &Pose, dt: f32) -> Vec<usize> {
+        let Some(clip) = glb.animations.get(self.clip) else {
+            return Vec::new();
+        };
+        if self.playing {
+            let duration = clip.duration();
+            self.time = if duration > 0.0 {
+                (self.time + dt) % duration
+            } else {
+                0.0
+            };
+        }
+        let mut pose = rest.clone();
+        clip.pose(&mut pose, self.time);
+        let weights = clip.weights(&glb.nodes, self.time);
+        let expressions = clip.expression_weights(self.time);
+        pose.apply(glb);
+        for (mesh, weights) in weights {
+            glb.meshes[mesh].weights = Some(weights);
+        }
+        if expressions.is_empty() {
+            return Vec::new();
+        }
+        for (name, weight) in expressions {
+            glb.expressions.set_weight(&name, weight);
+        }
+        glb.apply_expressions()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel(path: Path, interpolation: Interpolation, values: Vec<f32>) -> Channel {
+        Channel {
+            node: 0,
+            path: path,
+            interpolation: interpolation,
+            times: vec![1.0, 2.0],
+            values: values,
+        }
+    }
+
+    #[test]
+    fn interpolates_keys() {
+        let linear = channel(
+            Path::Translation,
+            Interpolation::Linear,
+            vec![0.0, 0.0, 0.0, 2.0, 4.0, 6.0],
+        );
+        assert_eq!(linear.sample(0.0), [0.0, 0.0, 0.0]);
+        assert_eq!(linear.sample(1.5), [1.0, 2.0, 3.0]);
+        assert_eq!(linear.sample(3.0), [2.0, 4.0, 6.0]);
+        let step = channel(
+            Path::Translation,
+            Interpolation::Step,
+            vec![0.0, 0.0, 0.0, 2.0, 4.0, 6.0],
+        );
+        assert_eq!(step.sample(1.9), [0.0, 0.0, 0.0]);
+        // a linear segment written as a cubic spline with the tangents of the slope.
+        let values = vec![0.0, 0.0, 2.0, 2.0, 2.0, 0.0];
+        let cubic = channel(Path::Weights, Interpolation::CubicSpline, values);
+        assert!((cubic.sample(1.25)[0] - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn slerps_rotations() {
+        let (s, c) = (f32::consts::FRAC_PI_4.sin(), f32::consts::FRAC_PI_4.cos());
+        let rotation = channel(
+            Path::Rotation,
+            Interpolation::Linear,
+            vec![0.0, 0.0, 0.0, 1.0, 0.0, s, 0.0, c],
+        );
+        let q = quaternion(&rotation.sample(1.5));
+        assert!((q.angle() - f32::consts::FRAC_PI_4).abs() < 1e-5, "{}", q.angle());
+    }
+
+    #[test]
+    fn samples_vrm_animation_tracks() {
+        let (s, c) = (f32::consts::FRAC_PI_8.sin(), f32::consts::FRAC_PI_8.cos());
+        let clip = Clip {
+            name: String::new(),
+            channels: Vec::new(),
+            expressions: vec![(
+                "happy".to_string(),
+                channel(Path::Weights, Interpolation::Linear, vec![0.0, 1.0]),
+            )],
+            look_at: Some(channel(
+                Path::Rotation,
+                Interpolation::Linear,
+                vec![0.0, s, 0.0, c, 0.0, s, 0.0, c],
+            )),
+        };
+        assert_eq!(clip.duration(), 2.0);
+        assert_eq!(clip.expression_weights(1.25), [("happy".to_string(), 0.25)]);
+        let (yaw, pitch) = clip.look_at_angles(1.5).unwrap();
+        assert!((yaw - 45.0).abs() < 1e-4 && pitch.abs() < 1e-4, "{} {}", yaw, pitch);
+    }
+}
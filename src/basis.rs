@@ -0,0 +1,529 @@
+use std::*;
+
+// the order in which the lengths of the code length codes are stored.
+const CODE_LENGTH_ORDER: [usize; 21] = [17, 18, 19, 20, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15, 16];
+
+// the ETC1 modifiers, selected by the endpoint intensity and then by the pixel selector.
+const MODIFIERS: [[i32; 4]; 8] = [
+    [-8, -2, 2, 8],
+    [-17, -5, 5, 17],
+    [-29, -9, 9, 29],
+    [-42, -13, 13, 42],
+    [-60, -18, 18, 60],
+    [-80, -24, 24, 80],
+    [-106, -33, 33, 106],
+    [-183, -47, 47, 183],
+];
+
+// the endpoint predictor symbol that repeats the previous one.
+const PRED_REPEAT: u32 = 256;
+const SELECTOR_RUN_MIN: u32 = 3;
+const SELECTOR_RUN_SYMBOLS: u32 = 64;
+
+// reads the bits LSB first. reading past the end gives zeros.
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+// a canonical Huffman code. the codes of each length are assigned in symbol order.
+struct Huffman {
+    counts: [u16; 17],
+    symbols: Vec<u16>,
+}
+
+struct Endpoint {
+    color: [i32; 3],
+    intensity: usize,
+}
+
+struct Codebooks {
+    endpoints: Vec<Endpoint>,
+    // a byte per row, two bits per pixel.
+    selectors: Vec<[u8; 4]>,
+    endpoint_pred: Huffman,
+    endpoint_delta: Huffman,
+    selector: Huffman,
+    selector_run: Huffman,
+    history_size: usize,
+}
+
+// the recently used selectors. a used entry moves halfway to the front.
+struct History {
+    values: Vec<usize>,
+    rover: usize,
+}
+
+impl<'a> Bits<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Bits { data: data, pos: 0 }
+    }
+
+    fn get(&mut self, count: u32) -> u32 {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = self.data.get(self.pos / 8).copied().unwrap_or(0);
+            value |= (((byte >> (self.pos % 8)) & 1) as u32) << i;
+            self.pos += 1;
+        }
+        value
+    }
+
+    // chunks of `bits` bits, each followed by a flag telling whether another chunk follows.
+    fn vlc(&mut self, bits: u32) -> u32 {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let chunk = self.get(bits + 1);
+            value |= (chunk & ((1 << bits) - 1)) << shift;
+            shift += bits;
+            if chunk >> bits == 0 || shift >= 32 {
+                return value;
+            }
+        }
+    }
+
+    // the codes are stored from the most significant bit.
+    fn symbol(&mut self, table: &Huffman) -> Option<u32> {
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        for &count in &table.counts[1..] {
+            code |= self.get(1) as usize;
+            let count = count as usize;
+            if code - first < count {
+                return Some(table.symbols[index + code - first] as u32);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        None
+    }
+
+    // the code lengths are themselves Huffman coded, with runs of zeros and repeats of the previous length.
+    fn table(&mut self) -> Option<Huffman> {
+        let total = self.get(14) as usize;
+        let count = self.get(5) as usize;
+        if total == 0 || count == 0 || count > CODE_LENGTH_ORDER.len() {
+            return None;
+        }
+        let mut code_lengths = [0; 21];
+        for &i in &CODE_LENGTH_ORDER[..count] {
+            code_lengths[i] = self.get(3) as u8;
+        }
+        let code_lengths = Huffman::new(&code_lengths)?;
+
+        let mut lengths = vec![0; total];
+        let mut i = 0;
+        while i < total {
+            let (value, run) = match self.symbol(&code_lengths)? {
+                length @ 0..=16 => (length as u8, 1),
+                17 => (0, self.get(3) + 3),
+                18 => (0, self.get(7) + 11),
+                code => {
+                    let previous = *lengths.get(i.checked_sub(1)?)?;
+                    if previous == 0 {
+                        return None;
+                    }
+                    (previous, if code == 19 { self.get(2) + 3 } else { self.get(6) + 7 })
+                }
+            };
+            lengths.get_mut(i..i + run as usize)?.fill(value);
+            i += run as usize;
+        }
+        Huffman::new(&lengths)
+    }
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Option<Self> {
+        let mut counts = [0; 17];
+        let mut symbols = Vec::new();
+        for length in 1..=16 {
+            for (symbol, _) in lengths.iter().enumerate().filter(|(_, l)| **l == length) {
+                counts[length as usize] += 1;
+                symbols.push(symbol as u16);
+            }
+        }
+        if symbols.is_empty() {
+            return None;
+        }
+        Some(Huffman {
+            counts: counts,
+            symbols: symbols,
+        })
+    }
+}
+
+impl History {
+    fn new(size: usize) -> Self {
+        History {
+            values: vec![0; size],
+            rover: size / 2,
+        }
+    }
+
+    fn add(&mut self, value: usize) {
+        self.values[self.rover] = value;
+        self.rover += 1;
+        if self.rover == self.values.len() {
+            self.rover = self.values.len() / 2;
+        }
+    }
+
+    fn get(&mut self, index: usize) -> Option<usize> {
+        let value = *self.values.get(index)?;
+        self.values.swap(index / 2, index);
+        Some(value)
+    }
+}
+
+// the colors are coded as deltas from the previous endpoint, with the table chosen by the previous value.
+fn decode_endpoints(data: &[u8], count: usize) -> Option<Vec<Endpoint>> {
+    let mut bits = Bits::new(data);
+    let color_tables = [bits.table()?, bits.table()?, bits.table()?];
+    let intensity_table = bits.table()?;
+    let grayscale = bits.get(1) == 1;
+
+    let mut endpoints = Vec::with_capacity(count);
+    let mut color = [16; 3];
+    let mut intensity = 0;
+    for _ in 0..count {
+        intensity = (intensity + bits.symbol(&intensity_table)? as usize) & 7;
+        for c in color.iter_mut().take(if grayscale { 1 } else { 3 }) {
+            let table = match *c {
+                ..=9 => &color_tables[0],
+                10..=21 => &color_tables[1],
+                _ => &color_tables[2],
+            };
+            *c = (*c + bits.symbol(table)? as i32) & 31;
+        }
+        if grayscale {
+            color = [color[0]; 3];
+        }
+        endpoints.push(Endpoint {
+            color: color,
+            intensity: intensity,
+        });
+    }
+    Some(endpoints)
+}
+
+// the rows are either raw or XORed with the rows of the previous selector.
+fn decode_selectors(data: &[u8], count: usize) -> Option<Vec<[u8; 4]>> {
+    let mut bits = Bits::new(data);
+    // the global and hybrid codebooks of old .basis files are not used in KTX2.
+    if bits.get(1) == 1 || bits.get(1) == 1 {
+        return None;
+    }
+    let table = match bits.get(1) {
+        1 => None,
+        _ => Some(bits.table()?),
+    };
+
+    let mut selectors = Vec::with_capacity(count);
+    let mut rows = [0; 4];
+    for i in 0..count {
+        for row in &mut rows {
+            *row = match &table {
+                Some(table) if i > 0 => bits.symbol(table)? as u8 ^ *row,
+                _ => bits.get(8) as u8,
+            };
+        }
+        selectors.push(rows);
+    }
+    Some(selectors)
+}
+
+// decodes a slice of ETC1S blocks into the RGB channels of `rgba`, or into its alpha channel from the green one.
+fn decode_slice(
+    books: &Codebooks,
+    data: &[u8],
+    width: usize,
+    height: usize,
+    rgba: &mut [u8],
+    alpha: bool,
+) -> Option<()> {
+    let (blocks_x, blocks_y) = (width.div_ceil(4), height.div_ceil(4));
+    let (endpoint_count, selector_count) = (books.endpoints.len(), books.selectors.len());
+    let mut bits = Bits::new(data);
+    let mut history = History::new(books.history_size);
+    let mut run = 0;
+    let (mut pred_bits, mut pred_last, mut pred_repeat) = (0, 0, 0);
+    let mut last_endpoint = 0;
+    // the endpoints of the row above and of the current one. the predictors of a 2x2 group are decoded on its
+    // top row and kept for the bottom one.
+    let mut above = vec![0; blocks_x];
+    let mut current = vec![0; blocks_x];
+    let mut preds_below = vec![0; blocks_x];
+
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            if bx % 2 == 0 {
+                if by % 2 == 0 {
+                    if pred_repeat > 0 {
+                        pred_repeat -= 1;
+                        pred_bits = pred_last;
+                    } else {
+                        pred_bits = bits.symbol(&books.endpoint_pred)?;
+                        if pred_bits == PRED_REPEAT {
+                            pred_repeat = bits.vlc(4) + 2;
+                            pred_bits = pred_last;
+                        } else {
+                            pred_last = pred_bits;
+                        }
+                    }
+                    preds_below[bx] = pred_bits >> 4;
+                } else {
+                    pred_bits = preds_below[bx];
+                }
+            }
+
+            // left, above, above left, or a delta from the last endpoint.
+            let endpoint = match pred_bits & 3 {
+                0 if bx > 0 => last_endpoint,
+                1 if by > 0 => above[bx],
+                2 if bx > 0 && by > 0 => above[bx - 1],
+                3 => (last_endpoint + bits.symbol(&books.endpoint_delta)? as usize) % endpoint_count,
+                _ => return None,
+            };
+            pred_bits >>= 2;
+            current[bx] = endpoint;
+            last_endpoint = endpoint;
+
+            // a selector, an index into the history, or the start of a run of the most recent selector.
+            let mut symbol = selector_count;
+            if run > 0 {
+                run -= 1;
+            } else {
+                symbol = bits.symbol(&books.selector)? as usize;
+                if symbol == selector_count + books.history_size {
+                    run = match bits.symbol(&books.selector_run)? {
+                        n if n == SELECTOR_RUN_SYMBOLS - 1 => bits.vlc(7) + SELECTOR_RUN_MIN,
+                        n => n + SELECTOR_RUN_MIN,
+                    } as usize;
+                    if run > blocks_x * blocks_y {
+                        return None;
+                    }
+                    run -= 1;
+                    symbol = selector_count;
+                }
+            }
+            let selector = match symbol.checked_sub(selector_count) {
+                Some(index) => history.get(index)?,
+                None => {
+                    if books.history_size > 0 {
+                        history.add(symbol);
+                    }
+                    symbol
+                }
+            };
+
+            let endpoint = books.endpoints.get(endpoint)?;
+            let rows = books.selectors.get(selector)?;
+            let base = endpoint.color.map(|c| (c << 3) | (c >> 2));
+            let colors = MODIFIERS[endpoint.intensity].map(|m| base.map(|c| (c + m).clamp(0, 255) as u8));
+            for (y, row) in rows.iter().enumerate().take(height - by * 4) {
+                for x in 0..(width - bx * 4).min(4) {
+                    let color = colors[((row >> (2 * x)) & 3) as usize];
+                    let pixel = &mut rgba[4 * ((by * 4 + y) * width + bx * 4 + x)..][..4];
+                    if alpha {
+                        pixel[3] = color[1];
+                    } else {
+                        pixel[..3].copy_from_slice(&color);
+                    }
+                }
+            }
+        }
+        mem::swap(&mut above, &mut current);
+    }
+    Some(())
+}
+
+// transcodes the levels of a BasisLZ supercompressed KTX2 image to RGBA8. the global data holds the codebooks shared
+// by the levels and the description of each level's RGB and alpha slices.
+pub fn transcode_etc1s(global: &[u8], levels: &[&[u8]], width: u32, height: u32) -> Option<Vec<Vec<u8>>> {
+    let read = |offset: usize, size: usize| {
+        let bytes = global.get(offset..offset + size)?;
+        Some(bytes.iter().rev().fold(0, |v, b| (v << 8) | *b as usize))
+    };
+    let mut offset = 20 + 20 * levels.len();
+    let mut sections = Vec::new();
+    for i in 0..3 {
+        let size = read(4 + 4 * i, 4)?;
+        sections.push(global.get(offset..offset + size)?);
+        offset += size;
+    }
+
+    let mut bits = Bits::new(sections[2]);
+    let books = Codebooks {
+        endpoints: decode_endpoints(sections[0], read(0, 2)?)?,
+        selectors: decode_selectors(sections[1], read(2, 2)?)?,
+        endpoint_pred: bits.table()?,
+        endpoint_delta: bits.table()?,
+        selector: bits.table()?,
+        selector_run: bits.table()?,
+        history_size: bits.get(13) as usize,
+    };
+    if books.endpoints.is_empty() || books.selectors.is_empty() {
+        return None;
+    }
+
+    let mut buffers = Vec::new();
+    for (level, data) in levels.iter().enumerate() {
+        let desc = 20 + 20 * level;
+        // P-frames are predicted from the previous frame of a video.
+        if read(desc, 4)? & 2 != 0 {
+            return None;
+        }
+        let width = (width >> level).max(1) as usize;
+        let height = (height >> level).max(1) as usize;
+        let mut rgba = vec![255; 4 * width * height];
+        for (slice, alpha) in [(desc + 4, false), (desc + 12, true)] {
+            let (start, size) = (read(slice, 4)?, read(slice + 4, 4)?);
+            if size > 0 || !alpha {
+                let data = data.get(start..start + size)?;
+                decode_slice(&books, data, width, height, &mut rgba, alpha)?;
+            }
+        }
+        buffers.push(rgba);
+    }
+    Some(buffers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Writer {
+        data: Vec<u8>,
+        pos: usize,
+    }
+
+    impl Writer {
+        fn put(&mut self, value: u32, count: u32) {
+            for i in 0..count {
+                if self.pos.is_multiple_of(8) {
+                    self.data.push(0);
+                }
+                *self.data.last_mut().unwrap() |= (((value >> i) & 1) as u8) << (self.pos % 8);
+                self.pos += 1;
+            }
+        }
+
+        fn symbol(&mut self, lengths: &[u8], symbol: usize) {
+            let mut code = 0;
+            for length in 1..=16 {
+                for (s, _) in lengths.iter().enumerate().filter(|(_, l)| **l == length) {
+                    if s == symbol {
+                        for i in (0..length).rev() {
+                            self.put((code >> i) & 1, 1);
+                        }
+                        return;
+                    }
+                    code += 1;
+                }
+                code <<= 1;
+            }
+            unreachable!();
+        }
+
+        // the code length codes have 5 bits each. the last three lengths are coded as a repeat.
+        fn table(&mut self, lengths: &[u8]) {
+            let code_lengths = [5; 21];
+            self.put(lengths.len() as u32, 14);
+            self.put(21, 5);
+            for _ in 0..21 {
+                self.put(5, 3);
+            }
+            let repeat = lengths.len() > 3
+                && lengths[lengths.len() - 4..]
+                    .iter()
+                    .all(|l| *l == lengths[lengths.len() - 1]);
+            let (lengths, repeat) = if repeat {
+                (&lengths[..lengths.len() - 3], true)
+            } else {
+                (lengths, false)
+            };
+            for &length in lengths {
+                self.symbol(&code_lengths, length as usize);
+            }
+            if repeat {
+                self.symbol(&code_lengths, 19);
+                self.put(0, 2);
+            }
+        }
+    }
+
+    #[test]
+    fn transcodes_etc1s() {
+        // two endpoints, coded as deltas from an intensity of 0 and a color of 16, 16, 16.
+        let mut endpoints = Writer::default();
+        for _ in 0..3 {
+            endpoints.table(&[5; 32]);
+        }
+        endpoints.table(&[3; 8]);
+        endpoints.put(0, 1);
+        for (intensity, color) in [(2, [15, 16, 0]), (2, [1, 8, 8])] {
+            endpoints.symbol(&[3; 8], intensity);
+            for delta in color {
+                endpoints.symbol(&[5; 32], delta);
+            }
+        }
+
+        // two selectors, the second one XORed with the first.
+        let mut selectors = Writer::default();
+        selectors.put(0, 3);
+        selectors.table(&[8; 256]);
+        for _ in 0..4 {
+            selectors.put(0b11100100, 8);
+        }
+        for delta in [0b11111111, 0b11100100, 0, 0] {
+            selectors.symbol(&[8; 256], delta);
+        }
+
+        let mut tables = Writer::default();
+        tables.table(&[9; 257]);
+        tables.table(&[1; 2]);
+        tables.table(&[3; 7]);
+        tables.table(&[6; 64]);
+        tables.put(4, 13);
+
+        // a delta to each endpoint, the first selector and the second one.
+        let mut slice = Writer::default();
+        slice.symbol(&[9; 257], 0b1111);
+        slice.symbol(&[1; 2], 0);
+        slice.symbol(&[3; 7], 0);
+        slice.symbol(&[1; 2], 1);
+        slice.symbol(&[3; 7], 1);
+
+        let mut global = Vec::new();
+        global.extend(2u16.to_le_bytes());
+        global.extend(2u16.to_le_bytes());
+        for section in [&endpoints, &selectors, &tables] {
+            global.extend((section.data.len() as u32).to_le_bytes());
+        }
+        global.extend(0u32.to_le_bytes());
+        for v in [0, 0, slice.data.len() as u32, 0, 0] {
+            global.extend(v.to_le_bytes());
+        }
+        for section in [&endpoints, &selectors, &tables] {
+            global.extend(&section.data);
+        }
+
+        let levels = transcode_etc1s(&global, &[&slice.data], 7, 3).unwrap();
+        assert_eq!(levels.len(), 1);
+        let rgba = &levels[0];
+        assert_eq!(rgba.len(), 4 * 7 * 3);
+        let pixel = |x: usize, y: usize| &rgba[4 * (y * 7 + x)..][..4];
+        // 31, 0 and 16 expand to 255, 0 and 132, shifted by the modifiers -29, -9, 9 and 29.
+        assert_eq!(pixel(0, 0), [226, 0, 103, 255]);
+        assert_eq!(pixel(1, 2), [246, 0, 123, 255]);
+        assert_eq!(pixel(3, 1), [255, 29, 161, 255]);
+        // 0, 8 and 24 expand to 0, 66 and 198, shifted by -60, -18, 18 and 60. the first rows of the second selector
+        // are reversed and zero.
+        assert_eq!(pixel(4, 0), [60, 126, 255, 255]);
+        assert_eq!(pixel(6, 1), [0, 6, 138, 255]);
+        assert_eq!(pixel(5, 2), [0, 48, 180, 255]);
+    }
+}
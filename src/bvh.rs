@@ -0,0 +1,222 @@
+use crate::*;
+use nalgebra::{UnitQuaternion, Vector3};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Position(usize),
+    Rotation(usize),
+}
+
+struct Parser<'a> {
+    tokens: str::SplitWhitespace<'a>,
+    nodes: Vec<scene::Node>,
+    // in the order of the values of a frame.
+    channels: Vec<(usize, Kind)>,
+}
+
+impl Parser<'_> {
+    fn next_f32(&mut self) -> Option<f32> {
+        self.tokens.next()?.parse().ok()
+    }
+
+    // the block after "ROOT name", "JOINT name" or "End Site".
+    fn joint(&mut self, name: String) -> Option<usize> {
+        if self.tokens.next()? != "{" {
+            return None;
+        }
+        let n = self.nodes.len();
+        self.nodes.push(scene::Node {
+            name: name,
+            ..Default::default()
+        });
+        loop {
+            match self.tokens.next()? {
+                "OFFSET" => {
+                    let offset = [self.next_f32()?, self.next_f32()?, self.next_f32()?];
+                    self.nodes[n].translation = Vector3::from(offset);
+                }
+                "CHANNELS" => {
+                    let count = self.tokens.next()?.parse::<usize>().ok()?;
+                    for _ in 0..count {
+                        let kind = match self.tokens.next()? {
+                            "Xposition" => Kind::Position(0),
+                            "Yposition" => Kind::Position(1),
+                            "Zposition" => Kind::Position(2),
+                            "Xrotation" => Kind::Rotation(0),
+                            "Yrotation" => Kind::Rotation(1),
+                            "Zrotation" => Kind::Rotation(2),
+                            _ => return None,
+                        };
+                        self.channels.push((n, kind));
+                    }
+                }
+                "JOINT" => {
+                    let name = self.tokens.next()?.to_string();
+                    let child = self.joint(name)?;
+                    self.nodes[n].children.push(child);
+                }
+                "End" => {
+                    if self.tokens.next()? != "Site" {
+                        return None;
+                    }
+                    let name = format!("{}_end", self.nodes[n].name);
+                    let child = self.joint(name)?;
+                    self.nodes[n].children.push(child);
+                }
+                "}" => return Some(n),
+                _ => return None,
+            }
+        }
+    }
+}
+
+// reads the skeleton into the nodes of a scene, in its rest pose, and the motion into its only clip. the rotations
+// are applied in the order of the channels and the positions replace the offsets.
+pub fn parse(text: &str) -> Option<scene::Glb> {
+    let mut parser = Parser {
+        tokens: text.split_whitespace(),
+        nodes: Vec::new(),
+        channels: Vec::new(),
+    };
+    if parser.tokens.next()? != "HIERARCHY" {
+        return None;
+    }
+    let mut roots = Vec::new();
+    loop {
+        match parser.tokens.next()? {
+            "ROOT" => {
+                let name = parser.tokens.next()?.to_string();
+                roots.push(parser.joint(name)?);
+            }
+            "MOTION" => break,
+            _ => return None,
+        }
+    }
+    if parser.tokens.next()? != "Frames:" {
+        return None;
+    }
+    let frames = parser.tokens.next()?.parse::<usize>().ok()?;
+    if parser.tokens.next()? != "Frame" || parser.tokens.next()? != "Time:" {
+        return None;
+    }
+    let frame_time = parser.next_f32()?;
+    let values = parser
+        .tokens
+        .map(|t| t.parse::<f32>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let width = parser.channels.len();
+    if values.len() != frames * width || frames == 0 {
+        return None;
+    }
+
+    let nodes = parser.nodes;
+    let times = (0..frames).map(|f| f as f32 * frame_time).collect::<Vec<_>>();
+    let mut channels = Vec::new();
+    for (n, node) in nodes.iter().enumerate() {
+        let columns = (parser.channels.iter().enumerate())
+            .filter(|(_, (node, _))| *node == n)
+            .map(|(i, (_, kind))| (i, *kind))
+            .collect::<Vec<_>>();
+        let mut translations = Vec::new();
+        let mut rotations = Vec::new();
+        for frame in values.chunks_exact(width) {
+            let mut translation = node.translation;
+            let mut rotation = UnitQuaternion::identity();
+            for (i, kind) in columns.iter() {
+                match kind {
+                    Kind::Position(axis) => translation[*axis] = frame[*i],
+                    Kind::Rotation(axis) => {
+                        let axis = Vector3::ith_axis(*axis);
+                        rotation *= UnitQuaternion::from_axis_angle(&axis, frame[*i].to_radians());
+                    }
+                }
+            }
+            translations.extend(translation.iter());
+            rotations.extend(rotation.coords.iter());
+        }
+        let has = |f: fn(&Kind) -> bool| columns.iter().any(|(_, kind)| f(kind));
+        if has(|k| matches!(k, Kind::Position(_))) {
+            channels.push(animation::Channel {
+                node: n,
+                path: animation::Path::Translation,
+                interpolation: animation::Interpolation::Linear,
+                times: times.clone(),
+                values: translations,
+            });
+        }
+        if has(|k| matches!(k, Kind::Rotation(_))) {
+            channels.push(animation::Channel {
+                node: n,
+                path: animation::Path::Rotation,
+                interpolation: animation::Interpolation::Linear,
+                times: times.clone(),
+                values: rotations,
+            });
+        }
+    }
+
+    Some(scene::Glb {
+        nodes: nodes,
+        roots: roots,
+        animations: vec![animation::Clip {
+            name: String::new(),
+            channels: channels,
+            expressions: Vec::new(),
+            look_at: None,
+        }],
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BVH: &str = "HIERARCHY
+ROOT Hips
+{
+  OFFSET 0 0 0
+  CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+  JOINT Spine
+  {
+    OFFSET 0 10 0
+    CHANNELS 3 Zrotation Xrotation Yrotation
+    End Site
+    {
+      OFFSET 0 10 0
+    }
+  }
+}
+MOTION
+Frames: 2
+Frame Time: 0.5
+0 90 0 0 0 0 0 0 0
+1 90 0 90 0 0 0 0 90
+";
+
+    #[test]
+    fn parses_the_skeleton_and_the_motion() {
+        let glb = parse(BVH).unwrap();
+        let names = glb.nodes.iter().map(|n| n.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["Hips", "Spine", "Spine_end"]);
+        assert_eq!(glb.nodes[0].children, [1]);
+        assert_eq!(glb.nodes[2].translation, Vector3::new(0.0, 10.0, 0.0));
+
+        let clip = &glb.animations[0];
+        assert_eq!(clip.channels.len(), 3);
+        assert_eq!(clip.duration(), 0.5);
+        let mut pose = animation::Pose::new(&glb);
+        clip.pose(&mut pose, 0.5);
+        assert_eq!(pose.translations[0], Vector3::new(1.0, 90.0, 0.0));
+        // the hips turn about z and the spine about y.
+        let x = pose.rotations[0] * Vector3::x();
+        assert!((x - Vector3::y()).norm() < 1e-5, "{}", x);
+        let z = pose.rotations[1] * Vector3::z();
+        assert!((z - Vector3::x()).norm() < 1e-5, "{}", z);
+    }
+
+    #[test]
+    fn rejects_truncated_motion() {
+        assert!(parse(&BVH[..BVH.len() - 4]).is_none());
+    }
+}
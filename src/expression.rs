@@ -0,0 +1,389 @@
+use crate::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Override {
+    None,
+    Block,
+    Blend,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Category {
+    Blink,
+    LookAt,
+    Mouth,
+    Other,
+}
+
+#[derive(Clone, Debug)]
+pub struct MorphTargetBind {
+    pub mesh: usize,
+    pub index: usize,
+    pub weight: f32,
+}
+
+// only the colors which map to the glTF material are applied. the MToon ones are kept for completeness.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorType {
+    Color,
+    EmissionColor,
+    ShadeColor,
+    MatcapColor,
+    RimColor,
+    OutlineColor,
+}
+
+#[derive(Clone, Debug)]
+pub struct MaterialColorBind {
+    pub material: usize,
+    pub color_type: ColorType,
+    pub target: [f32; 4],
+}
+
+#[derive(Clone, Debug)]
+pub struct TextureTransformBind {
+    pub material: usize,
+    pub scale: [f32; 2],
+    pub offset: [f32; 2],
+}
+
+#[derive(Clone, Debug)]
+pub struct Expression {
+    pub name: String,
+    pub preset: bool,
+    pub is_binary: bool,
+    pub override_blink: Override,
+    pub override_look_at: Override,
+    pub override_mouth: Override,
+    pub morph_target_binds: Vec<MorphTargetBind>,
+    pub material_color_binds: Vec<MaterialColorBind>,
+    pub texture_transform_binds: Vec<TextureTransformBind>,
+}
+
+struct BaseMaterial {
+    material: usize,
+    base_color_factor: [f32; 4],
+    emissive_factor: [f32; 3],
+    base_color_transform: scene::TextureTransform,
+    emissive_transform: scene::TextureTransform,
+}
+
+// the values of the bound meshes and materials as loaded, which the weighted binds are added to.
+struct Base {
+    mesh_weights: Vec<(usize, Vec<f32>)>,
+    materials: Vec<BaseMaterial>,
+}
+
+pub struct Expressions {
+    pub list: Vec<Expression>,
+    weights: Vec<f32>,
+    base: Base,
+}
+
+impl Expression {
+    pub const PRESETS: [&'static str; 18] = [
+        "happy",
+        "angry",
+        "sad",
+        "relaxed",
+        "surprised",
+        "aa",
+        "ih",
+        "ou",
+        "ee",
+        "oh",
+        "blink",
+        "blinkLeft",
+        "blinkRight",
+        "lookUp",
+        "lookDown",
+        "lookLeft",
+        "lookRight",
+        "neutral",
+    ];
+
+    pub fn category(&self) -> Category {
+        if !self.preset {
+            return Category::Other;
+        }
+        match self.name.as_str() {
+            "blink" | "blinkLeft" | "blinkRight" => Category::Blink,
+            "lookUp" | "lookDown" | "lookLeft" | "lookRight" => Category::LookAt,
+            "aa" | "ih" | "ou" | "ee" | "oh" => Category::Mouth,
+            _ => Category::Other,
+        }
+    }
+}
+
+impl Expressions {
+    pub fn new(list: Vec<Expression>, meshes: &[scene::Mesh], materials: &[scene::Material]) -> Self {
+        let mut mesh_weights = Vec::new();
+        let mut base_materials = Vec::new();
+        for expression in list.iter() {
+            for bind in expression.morph_target_binds.iter() {
+                if !mesh_weights.iter().any(|(m, _)| *m == bind.mesh) {
+                    let weights = meshes[bind.mesh].weights.clone().unwrap_or_default();
+                    mesh_weights.push((bind.mesh, weights));
+                }
+            }
+            let bound = expression.material_color_binds.iter().map(|b| b.material);
+            let bound = bound.chain(expression.texture_transform_binds.iter().map(|b| b.material));
+            for material in bound {
+                if !base_materials.iter().any(|m: &BaseMaterial| m.material == material) {
+                    let m = &materials[material];
+                    let transform =
+                        |t: &Option<scene::Texture>| t.as_ref().map_or_else(Default::default, |t| t.transform);
+                    base_materials.push(BaseMaterial {
+                        material: material,
+                        base_color_factor: m.base_color_factor,
+                        emissive_factor: m.emissive_factor,
+                        base_color_transform: transform(&m.base_color_texture),
+                        emissive_transform: transform(&m.emissive_texture),
+                    });
+                }
+            }
+        }
+        Expressions {
+            weights: vec![0.0; list.len()],
+            list: list,
+            base: Base {
+                mesh_weights: mesh_weights,
+                materials: base_materials,
+            },
+        }
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.list.iter().position(|e| e.name == name)
+    }
+
+    pub fn weight(&self, name: &str) -> Option<f32> {
+        Some(self.weights[self.find(name)?])
+    }
+
+    // returns false if the model has no such expression.
+    pub fn set_weight(&mut self, name: &str, weight: f32) -> bool {
+        let Some(i) = self.find(name) else {
+            return false;
+        };
+        self.weights[i] = weight.clamp(0.0, 1.0);
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.weights.fill(0.0);
+    }
+
+    // the weights after rounding the binary expressions and suppressing the blink, lookAt and mouth expressions
+    // overridden by the others.
+    pub fn effective_weights(&self) -> Vec<f32> {
+        let mut weights = self
+            .list
+            .iter()
+            .zip(self.weights.iter())
+            .map(|(e, w)| {
+                if e.is_binary {
+                    if *w > 0.5 { 1.0 } else { 0.0 }
+                } else {
+                    *w
+                }
+            })
+            .collect::<Vec<_>>();
+        let mut suppress = [0.0f32; 3];
+        for (e, w) in self.list.iter().zip(weights.iter()) {
+            let overrides = [e.override_blink, e.override_look_at, e.override_mouth];
+            for (s, o) in suppress.iter_mut().zip(overrides) {
+                *s = s.max(match o {
+                    Override::None => 0.0,
+                    Override::Block if *w > 0.0 => 1.0,
+                    Override::Block => 0.0,
+                    Override::Blend => *w,
+                });
+            }
+        }
+        for (e, w) in self.list.iter().zip(weights.iter_mut()) {
+            let s = match e.category() {
+                Category::Blink => suppress[0],
+                Category::LookAt => suppress[1],
+                Category::Mouth => suppress[2],
+                Category::Other => 0.0,
+            };
+            *w *= 1.0 - s;
+        }
+        weights
+    }
+
+    // writes the morph weights and the material values. returns the materials which have to be uploaded again.
+    pub fn apply(&self, meshes: &mut [scene::Mesh], materials: &mut [scene::Material]) -> Vec<usize> {
+        for (mesh, weights) in self.base.mesh_weights.iter() {
+            meshes[*mesh].weights = Some(weights.clone());
+        }
+        for base in self.base.materials.iter() {
+            let m = &mut materials[base.material];
+            m.base_color_factor = base.base_color_factor;
+            m.emissive_factor = base.emissive_factor;
+            if let Some(t) = &mut m.base_color_texture {
+                t.transform = base.base_color_transform;
+            }
+            if let Some(t) = &mut m.emissive_texture {
+                t.transform = base.emissive_transform;
+            }
+        }
+
+        for (expression, w) in self.list.iter().zip(self.effective_weights()) {
+            if w == 0.0 {
+                continue;
+            }
+            for bind in expression.morph_target_binds.iter() {
+                let weights = meshes[bind.mesh].weights.get_or_insert_with(Vec::new);
+                if weights.len() <= bind.index {
+                    weights.resize(bind.index + 1, 0.0);
+                }
+                weights[bind.index] += w * bind.weight;
+            }
+            for bind in expression.material_color_binds.iter() {
+                let Some(base) = self.base.materials.iter().find(|m| m.material == bind.material) else {
+                    continue;
+                };
+                let m = &mut materials[bind.material];
+                match bind.color_type {
+                    ColorType::Color => blend(&mut m.base_color_factor, &base.base_color_factor, &bind.target, w),
+                    ColorType::EmissionColor => blend(&mut m.emissive_factor, &base.emissive_factor, &bind.target, w),
+                    _ => (),
+                }
+            }
+            for bind in expression.texture_transform_binds.iter() {
+                let Some(base) = self.base.materials.iter().find(|m| m.material == bind.material) else {
+                    continue;
+                };
+                let m = &mut materials[bind.material];
+                let slots = [
+                    (&mut m.base_color_texture, &base.base_color_transform),
+                    (&mut m.emissive_texture, &base.emissive_transform),
+                ];
+                for (t, base) in slots {
+                    if let Some(t) = t {
+                        blend(&mut t.transform.scale, &base.scale, &bind.scale, w);
+                        blend(&mut t.transform.offset, &base.offset, &bind.offset, w);
+                    }
+                }
+            }
+        }
+        self.base.materials.iter().map(|m| m.material).collect()
+    }
+}
+
+// moves the values from the base towards the target by the weight. the expressions add up.
+fn blend(values: &mut [f32], base: &[f32], target: &[f32], w: f32) {
+    for ((v, b), t) in values.iter_mut().zip(base).zip(target) {
+        *v += w * (t - b);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expression(name: &str, is_binary: bool, override_mouth: Override) -> Expression {
+        Expression {
+            name: name.to_string(),
+            preset: Expression::PRESETS.contains(&name),
+            is_binary: is_binary,
+            override_blink: Override::None,
+            override_look_at: Override::None,
+            override_mouth: override_mouth,
+            morph_target_binds: vec![MorphTargetBind {
+                mesh: 0,
+                index: 0,
+                weight: 1.0,
+            }],
+            material_color_binds: Vec::new(),
+            texture_transform_binds: Vec::new(),
+        }
+    }
+
+    fn expressions(list: Vec<Expression>) -> (Expressions, Vec<scene::Mesh>) {
+        let meshes = vec![scene::Mesh {
+            primitives: Vec::new(),
+            weights: Some(vec![0.25]),
+        }];
+        (Expressions::new(list, &meshes, &[]), meshes)
+    }
+
+    #[test]
+    fn rounds_binary_expressions() {
+        let (mut e, _) = expressions(vec![expression("happy", true, Override::None)]);
+        e.set_weight("happy", 0.4);
+        assert_eq!(e.effective_weights(), [0.0]);
+        e.set_weight("happy", 0.6);
+        assert_eq!(e.effective_weights(), [1.0]);
+    }
+
+    #[test]
+    fn overrides_mouth() {
+        let list = vec![
+            expression("surprised", false, Override::Blend),
+            expression("angry", false, Override::Block),
+            expression("aa", false, Override::None),
+        ];
+        let (mut e, _) = expressions(list);
+        e.set_weight("aa", 1.0);
+        e.set_weight("surprised", 0.25);
+        assert_eq!(e.effective_weights(), [0.25, 0.0, 0.75]);
+        e.set_weight("angry", 0.1);
+        assert_eq!(e.effective_weights(), [0.25, 0.1, 0.0]);
+        assert!(!e.set_weight("custom", 1.0));
+    }
+
+    #[test]
+    fn adds_morph_weights_to_the_loaded_ones() {
+        let list = vec![
+            expression("happy", false, Override::None),
+            expression("custom", false, Override::None),
+        ];
+        let (mut e, mut meshes) = expressions(list);
+        e.set_weight("happy", 0.5);
+        e.set_weight("custom", 0.125);
+        e.apply(&mut meshes, &mut []);
+        assert_eq!(meshes[0].weights, Some(vec![0.875]));
+        e.clear();
+        e.apply(&mut meshes, &mut []);
+        assert_eq!(meshes[0].weights, Some(vec![0.25]));
+    }
+
+    #[test]
+    fn blends_texture_transforms_from_the_base() {
+        let mut e = expression("happy", false, Override::None);
+        e.morph_target_binds.clear();
+        e.texture_transform_binds.push(TextureTransformBind {
+            material: 0,
+            scale: [4.0, 1.0],
+            offset: [1.0, 0.0],
+        });
+        let transform = scene::TextureTransform {
+            offset: [0.5, 0.0],
+            rotation: 0.0,
+            scale: [2.0, 1.0],
+        };
+        let mut materials = vec![scene::Material {
+            base_color_factor: [1.0; 4],
+            base_color_texture: Some(scene::Texture {
+                wrap_s: true,
+                wrap_t: true,
+                texcoord: 0,
+                transform: transform,
+                index: 0,
+            }),
+            emissive_factor: [0.0; 3],
+            emissive_texture: None,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            occlusion_texture: None,
+        }];
+        let mut e = Expressions::new(vec![e], &[], &materials);
+        e.set_weight("happy", 0.5);
+        assert_eq!(e.apply(&mut [], &mut materials), [0]);
+        let t = materials[0].base_color_texture.as_ref().unwrap().transform;
+        assert_eq!((t.scale, t.offset), ([3.0, 1.0], [0.75, 0.0]));
+    }
+}
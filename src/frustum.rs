@@ -0,0 +1,33 @@
+use crate::*;
+use nalgebra::{Matrix4, Vector4};
+
+pub struct Frustum {
+    planes: [Vector4<f32>; 5],
+}
+
+impl Frustum {
+    // planes of the clip space of Renderer: -w <= x, y <= w and w > 0.
+    const PLANES: [[f32; 4]; 5] = [
+        [1.0, 0.0, 0.0, 1.0],
+        [-1.0, 0.0, 0.0, 1.0],
+        [0.0, 1.0, 0.0, 1.0],
+        [0.0, -1.0, 0.0, 1.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ];
+
+    pub fn new(m_clip: &Matrix4<f32>) -> Self {
+        let m = m_clip.transpose();
+        Frustum {
+            planes: Self::PLANES.map(|p| m * Vector4::from(p)),
+        }
+    }
+
+    pub fn intersects(&self, aabb: &scene::Aabb) -> bool {
+        self.planes.iter().all(|p| {
+            let x = if p[0] >= 0.0 { aabb.max[0] } else { aabb.min[0] };
+            let y = if p[1] >= 0.0 { aabb.max[1] } else { aabb.min[1] };
+            let z = if p[2] >= 0.0 { aabb.max[2] } else { aabb.min[2] };
+            p[0] * x + p[1] * y + p[2] * z + p[3] >= 0.0
+        })
+    }
+}
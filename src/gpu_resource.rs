@@ -4,19 +4,55 @@ use wgpu::util::DeviceExt;
 #[repr(C, align(16))]
 struct MaterialUniform {
     base_color_factor: [f32; 4],
+    emissive_factor: [f32; 4],
+    base_color_transform: [[f32; 4]; 2],
+    emissive_transform: [[f32; 4]; 2],
     base_color_texcoord: u32,
+    emissive_texcoord: u32,
+}
+
+// JOINTS_0 and WEIGHTS_0 converted to a single format.
+#[repr(C)]
+struct SkinVertex {
+    joints: [u16; 4],
+    weights: [f32; 4],
 }
 
 pub struct GpuResource {
     pub material_layout: wgpu::BindGroupLayout,
+    pub morph_layout: wgpu::BindGroupLayout,
     pub sampler: wgpu::Sampler,
-    pub blob: Option<wgpu::Buffer>,
-    pub images: Vec<Option<(wgpu::TextureView, wgpu::Texture)>>,
+    pub white: GpuImage,
+    // bound in place of the images which are still being decoded.
+    pub placeholder: GpuImage,
+}
+
+// images are stored without the sRGB suffix and viewed with or without it depending on the material slot. the
+// views keep the texture alive.
+#[derive(Clone)]
+pub struct GpuImage {
+    pub srgb: wgpu::TextureView,
+    pub linear: wgpu::TextureView,
+}
+
+pub struct GpuModel {
+    // one buffer per view used by the primitives. views holding images only are not uploaded.
+    pub views: Vec<Option<wgpu::Buffer>>,
+    // per mesh and primitive. the primitives without a skin are bound to zero weights.
+    pub skin_vertices: Vec<Vec<Option<wgpu::Buffer>>>,
+    pub no_skin: wgpu::Buffer,
+    // the position and normal deltas of the morph targets of all the primitives, and the offset of each primitive.
+    pub morph_group: wgpu::BindGroup,
+    pub morph_offsets: Vec<Vec<u32>>,
+    // the indices of the first person variants, per mesh and primitive.
+    pub head_less: Vec<Vec<Option<wgpu::Buffer>>>,
+    // per glTF texture.
+    pub textures: Vec<Option<GpuImage>>,
     pub materials: Vec<(wgpu::BindGroup, wgpu::Buffer)>,
 }
 
 impl GpuResource {
-    pub fn new(device: &wgpu::Device) -> Self {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
         let material_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             entries: &[
                 wgpu::BindGroupLayoutEntry {
@@ -45,10 +81,34 @@ impl GpuResource {
                     ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                     count: None,
                 },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
             ],
             label: None,
         });
 
+        let morph_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: None,
+        });
+
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::Repeat,
             address_mode_v: wgpu::AddressMode::Repeat,
@@ -58,16 +118,20 @@ impl GpuResource {
             ..Default::default()
         });
 
+        let white = Self::create_texture(device, queue, &scene::Image::rgba8(1, 1, vec![255; 4]));
+
+        let placeholder = Self::create_texture(device, queue, &scene::Image::rgba8(1, 1, vec![128, 128, 128, 255]));
+
         GpuResource {
             material_layout: material_layout,
+            morph_layout: morph_layout,
             sampler: sampler,
-            blob: None,
-            images: Vec::new(),
-            materials: Vec::new(),
+            white: white,
+            placeholder: placeholder,
         }
     }
 
-    pub fn vertex_layouts<'a>(&'a self) -> [wgpu::VertexBufferLayout<'a>; 4] {
+    pub fn vertex_layouts<'a>(&'a self) -> [wgpu::VertexBufferLayout<'a>; 5] {
         [
             wgpu::VertexBufferLayout {
                 array_stride: 4 * 3,
@@ -105,129 +169,545 @@ impl GpuResource {
                     format: wgpu::VertexFormat::Float32x2,
                 }],
             },
+            wgpu::VertexBufferLayout {
+                array_stride: mem::size_of::<SkinVertex>() as u64,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &[
+                    wgpu::VertexAttribute {
+                        offset: 0,
+                        shader_location: 12,
+                        format: wgpu::VertexFormat::Uint16x4,
+                    },
+                    wgpu::VertexAttribute {
+                        offset: 8,
+                        shader_location: 13,
+                        format: wgpu::VertexFormat::Float32x4,
+                    },
+                ],
+            },
         ]
     }
 
-    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, scene: &scene::Glb) {
-        self.blob = Some(device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: None,
-            contents: &scene.blob,
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::INDEX,
-        }));
-
-        self.images.clear();
-        for image in scene.images.iter() {
-            let image = match image {
-                Some(image) => {
-                    let size = wgpu::Extent3d {
-                        width: image.dims[0],
-                        height: image.dims[1],
-                        depth_or_array_layers: 1,
-                    };
-                    let texture = device.create_texture(&wgpu::TextureDescriptor {
-                        size: size,
-                        mip_level_count: 1,
-                        sample_count: 1,
-                        dimension: wgpu::TextureDimension::D2,
-                        format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                        label: None,
-                        view_formats: &[],
-                    });
-                    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-
-                    queue.write_texture(
-                        wgpu::TexelCopyTextureInfo {
-                            texture: &texture,
-                            mip_level: 0,
-                            origin: wgpu::Origin3d::ZERO,
-                            aspect: wgpu::TextureAspect::All,
-                        },
-                        &image.buffer,
-                        wgpu::TexelCopyBufferLayout {
-                            offset: 0,
-                            bytes_per_row: Some(4 * image.dims[0]),
-                            rows_per_image: Some(image.dims[1]),
-                        },
-                        size,
-                    );
-
-                    Some((view, texture))
+    pub fn upload(&self, device: &wgpu::Device, scene: &scene::Glb, blob: &[u8]) -> GpuModel {
+        let mut usages = scene
+            .views
+            .iter()
+            .map(|view| match view.target {
+                Some(34962) => wgpu::BufferUsages::VERTEX,
+                Some(34963) => wgpu::BufferUsages::INDEX,
+                _ => wgpu::BufferUsages::empty(),
+            })
+            .collect::<Vec<_>>();
+        // the target is optional, so the usage is also derived from the primitives.
+        for primitive in scene.meshes.iter().flat_map(|m| m.primitives.iter()) {
+            let attributes = &primitive.attributes;
+            for accessor in [
+                attributes.position,
+                attributes.normal,
+                attributes.texcoord_0,
+                attributes.texcoord_1,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                usages[scene.accessors[accessor].view] |= wgpu::BufferUsages::VERTEX;
+            }
+            if let Some(indices) = primitive.indices {
+                usages[scene.accessors[indices].view] |= wgpu::BufferUsages::INDEX;
+            }
+        }
+        let views = scene
+            .views
+            .iter()
+            .zip(usages)
+            .map(|(view, usage)| {
+                if usage.is_empty() || view.length == 0 {
+                    return None;
                 }
-                None => None,
-            };
-            self.images.push(image);
+                Some(device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: None,
+                    contents: &blob[view.offset..view.offset + view.length],
+                    usage: usage,
+                }))
+            })
+            .collect();
+
+        let skin_vertices = scene
+            .meshes
+            .iter()
+            .map(|mesh| {
+                mesh.primitives
+                    .iter()
+                    .map(|primitive| {
+                        let vertices = Self::skin_vertices(scene, primitive, blob)?;
+                        Some(device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: None,
+                            contents: unsafe { utils::slice_as_bytes(&vertices) },
+                            usage: wgpu::BufferUsages::VERTEX,
+                        }))
+                    })
+                    .collect()
+            })
+            .collect();
+        let head_less = scene
+            .meshes
+            .iter()
+            .map(|mesh| {
+                mesh.primitives
+                    .iter()
+                    .map(|primitive| match primitive.head_less_indices.as_deref() {
+                        None | Some([]) => None,
+                        Some(indices) => Some(device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: None,
+                            contents: unsafe { utils::slice_as_bytes(indices) },
+                            usage: wgpu::BufferUsages::INDEX,
+                        })),
+                    })
+                    .collect()
+            })
+            .collect();
+        let vertex_count = scene
+            .meshes
+            .iter()
+            .flat_map(|m| m.primitives.iter())
+            .filter_map(|p| Some(scene.accessors[p.attributes.position?].count))
+            .max()
+            .unwrap_or(0);
+        let no_skin = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (vertex_count.max(1) * mem::size_of::<SkinVertex>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX,
+            mapped_at_creation: false,
+        });
+
+        let mut deltas = Vec::new();
+        let morph_offsets = scene
+            .meshes
+            .iter()
+            .map(|mesh| {
+                mesh.primitives
+                    .iter()
+                    .map(|primitive| {
+                        let offset = deltas.len() as u32;
+                        Self::morph_deltas(&mut deltas, scene, primitive, blob);
+                        offset
+                    })
+                    .collect()
+            })
+            .collect();
+        if deltas.is_empty() {
+            deltas.push([0.0; 4]);
         }
+        let morph_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: unsafe { utils::slice_as_bytes(&deltas) },
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let morph_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.morph_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: morph_buffer.as_entire_binding(),
+            }],
+            label: None,
+        });
+
+        // textures without an image in the blob are never decoded.
+        let textures = scene
+            .textures
+            .iter()
+            .map(
+                |images| match images.iter().any(|i| matches!(scene.images.get(*i), Some(Some(_)))) {
+                    true => None,
+                    false => Some(self.white.clone()),
+                },
+            )
+            .collect::<Vec<_>>();
 
-        self.materials.clear();
+        let mut materials = Vec::new();
         for material in scene.materials.iter() {
-            let uniform = MaterialUniform {
-                base_color_factor: material.base_color_factor,
-                base_color_texcoord: material.base_color_texture.as_ref().unwrap().texcoord as u32,
-            };
+            let uniform = Self::material_uniform(material);
             let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: None,
                 contents: unsafe { utils::as_bytes(&uniform) },
                 usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             });
-            let group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-                layout: &self.material_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: buffer.as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::TextureView(
-                            &self.images[material.base_color_texture.as_ref().unwrap().image]
-                                .as_ref()
-                                .unwrap()
-                                .0,
-                        ),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: wgpu::BindingResource::Sampler(&self.sampler),
-                    },
-                ],
-                label: None,
-            });
-            self.materials.push((group, buffer));
+            let group = self.create_material_group(device, &textures, &buffer, material);
+            materials.push((group, buffer));
         }
+
+        GpuModel {
+            views: views,
+            skin_vertices: skin_vertices,
+            no_skin: no_skin,
+            morph_group: morph_group,
+            morph_offsets: morph_offsets,
+            head_less: head_less,
+            textures: textures,
+            materials: materials,
+        }
+    }
+
+    fn skin_vertices(scene: &scene::Glb, primitive: &scene::Primitive, blob: &[u8]) -> Option<Vec<SkinVertex>> {
+        let joints = &scene.accessors[primitive.attributes.joints_0?];
+        let weights = &scene.accessors[primitive.attributes.weights_0?];
+        if joints.component_count != 4 || weights.component_count != 4 || joints.count != weights.count {
+            return None;
+        }
+        let joints = joints.read(&scene.views[joints.view], blob, false);
+        let weights = weights.read(&scene.views[weights.view], blob, true);
+        let vertices = joints
+            .chunks(4)
+            .zip(weights.chunks(4))
+            .map(|(j, w)| SkinVertex {
+                joints: [j[0] as u16, j[1] as u16, j[2] as u16, j[3] as u16],
+                weights: [w[0], w[1], w[2], w[3]],
+            })
+            .collect();
+        Some(vertices)
+    }
+
+    // appends the deltas as a position and a normal per target and vertex. missing attributes are zero.
+    fn morph_deltas(deltas: &mut Vec<[f32; 4]>, scene: &scene::Glb, primitive: &scene::Primitive, blob: &[u8]) {
+        let Some(position) = primitive.attributes.position else {
+            return;
+        };
+        let count = scene.accessors[position].count;
+        let read = |index: Option<usize>| {
+            let accessor = &scene.accessors[index?];
+            if accessor.component_count != 3 || accessor.count != count {
+                return None;
+            }
+            Some(accessor.read(&scene.views[accessor.view], blob, true))
+        };
+        for target in primitive.targets.iter() {
+            let positions = read(target.position).unwrap_or_else(|| vec![0.0; count * 3]);
+            let normals = read(target.normal).unwrap_or_else(|| vec![0.0; count * 3]);
+            for (p, n) in positions.chunks(3).zip(normals.chunks(3)) {
+                deltas.push([p[0], p[1], p[2], 0.0]);
+                deltas.push([n[0], n[1], n[2], 0.0]);
+            }
+        }
+    }
+
+    fn material_uniform(material: &scene::Material) -> MaterialUniform {
+        let [er, eg, eb] = material.emissive_factor;
+        let transform = |t: &Option<scene::Texture>| t.as_ref().map_or_else(Default::default, |t| t.transform).rows();
+        MaterialUniform {
+            base_color_factor: material.base_color_factor,
+            emissive_factor: [er, eg, eb, 0.0],
+            base_color_transform: transform(&material.base_color_texture),
+            emissive_transform: transform(&material.emissive_texture),
+            base_color_texcoord: material.base_color_texture.as_ref().map_or(0, |t| t.texcoord as u32),
+            emissive_texcoord: material.emissive_texture.as_ref().map_or(0, |t| t.texcoord as u32),
+        }
+    }
+
+    // rewrites the uniform after the factors or the texture transforms of the material have been changed.
+    pub fn update_material(&self, queue: &wgpu::Queue, model: &GpuModel, scene: &scene::Glb, index: usize) {
+        let (Some(material), Some((_, buffer))) = (scene.materials.get(index), model.materials.get(index)) else {
+            return;
+        };
+        let uniform = Self::material_uniform(material);
+        queue.write_buffer(buffer, 0, unsafe { utils::as_bytes(&uniform) });
+    }
+
+    // uploads the decoded image of the textures, or the white texture if decoding failed, and rebinds the materials
+    // using them.
+    pub fn set_image(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        model: &mut GpuModel,
+        scene: &scene::Glb,
+        textures: &[usize],
+        image: Option<&scene::Image>,
+    ) -> Result<(), String> {
+        let (image, result) = match image {
+            Some(image) if device.features().contains(image.format.required_features()) => {
+                (Self::create_texture(device, queue, image), Ok(()))
+            }
+            Some(image) => (
+                self.white.clone(),
+                Err(format!("{:?} is not supported by the adapter", image.format)),
+            ),
+            None => (self.white.clone(), Ok(())),
+        };
+        for index in textures {
+            if let Some(slot) = model.textures.get_mut(*index) {
+                *slot = Some(image.clone());
+            }
+        }
+
+        for (material, (group, buffer)) in scene.materials.iter().zip(model.materials.iter_mut()) {
+            if material.textures().any(|(t, _)| textures.contains(&t.index)) {
+                *group = self.create_material_group(device, &model.textures, buffer, material);
+            }
+        }
+        result
+    }
+
+    fn create_material_group(
+        &self,
+        device: &wgpu::Device,
+        textures: &[Option<GpuImage>],
+        buffer: &wgpu::Buffer,
+        material: &scene::Material,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.material_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(self.texture_view(
+                        textures,
+                        &material.base_color_texture,
+                        scene::ColorSpace::Srgb,
+                    )),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(self.texture_view(
+                        textures,
+                        &material.emissive_texture,
+                        scene::ColorSpace::Srgb,
+                    )),
+                },
+            ],
+            label: None,
+        })
+    }
+
+    fn create_texture(device: &wgpu::Device, queue: &wgpu::Queue, image: &scene::Image) -> GpuImage {
+        let format = image.format.remove_srgb_suffix();
+        let srgb_format = view_format(format, scene::ColorSpace::Srgb);
+        let view_formats = if srgb_format != format {
+            vec![srgb_format]
+        } else {
+            Vec::new()
+        };
+        let size = wgpu::Extent3d {
+            width: image.dims[0],
+            height: image.dims[1],
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: size,
+            mip_level_count: 1 + image.mips.len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            label: None,
+            view_formats: &view_formats,
+        });
+        let create_view = |format| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                format: Some(format),
+                ..Default::default()
+            })
+        };
+        let srgb = create_view(srgb_format);
+        let linear = create_view(view_format(format, scene::ColorSpace::Linear));
+
+        let (block_w, block_h) = image.format.block_dimensions();
+        let block_size = image.format.block_copy_size(None).unwrap();
+        for (level, buffer) in iter::once(&image.buffer).chain(image.mips.iter()).enumerate() {
+            // compressed levels are copied in whole blocks, even where the level is smaller than a block.
+            let size = size.mip_level_size(level as u32, wgpu::TextureDimension::D2);
+            let blocks_w = size.width.div_ceil(block_w);
+            let blocks_h = size.height.div_ceil(block_h);
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &texture,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                buffer,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(blocks_w * block_size),
+                    rows_per_image: Some(blocks_h),
+                },
+                wgpu::Extent3d {
+                    width: blocks_w * block_w,
+                    height: blocks_h * block_h,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+
+        GpuImage {
+            srgb: srgb,
+            linear: linear,
+        }
+    }
+
+    fn texture_view<'a>(
+        &'a self,
+        textures: &'a [Option<GpuImage>],
+        texture: &Option<scene::Texture>,
+        color_space: scene::ColorSpace,
+    ) -> &'a wgpu::TextureView {
+        let image = match texture {
+            Some(texture) => match textures.get(texture.index) {
+                Some(Some(image)) => image,
+                Some(None) => &self.placeholder,
+                None => &self.white,
+            },
+            None => &self.white,
+        };
+        match color_space {
+            scene::ColorSpace::Srgb => &image.srgb,
+            scene::ColorSpace::Linear => &image.linear,
+        }
+    }
+}
+
+// the format an image is viewed with in a slot of the given color space, whatever the format it was decoded to.
+fn view_format(format: wgpu::TextureFormat, color_space: scene::ColorSpace) -> wgpu::TextureFormat {
+    match color_space {
+        scene::ColorSpace::Srgb => format.add_srgb_suffix(),
+        scene::ColorSpace::Linear => format.remove_srgb_suffix(),
+    }
+}
+
+impl GpuModel {
+    pub fn draw_primitive<'a>(
+        &'a self,
+        pass: &mut wgpu::RenderPass<'a>,
+        glb: &scene::Glb,
+        mesh: usize,
+        primitive: usize,
+        head_less: bool,
+        instances: ops::Range<u32>,
+    ) {
+        let skin = match &self.skin_vertices[mesh][primitive] {
+            Some(buffer) => buffer.slice(..),
+            None => self.no_skin.slice(..),
+        };
+        let head_less = match head_less {
+            true => Some(self.head_less[mesh][primitive].as_ref()),
+            false => None,
+        };
+        let primitive = &glb.meshes[mesh].primitives[primitive];
+        let slice = |index: usize| {
+            let accessor = &glb.accessors[index];
+            let buffer = self.views[accessor.view].as_ref()?;
+            match accessor.byte_length() {
+                0 => None,
+                n => Some(buffer.slice(accessor.offset as u64..(accessor.offset + n) as u64)),
+            }
+        };
+        let Some(position) = primitive.attributes.position else {
+            return;
+        };
+        let Some(normal) = primitive.attributes.normal else {
+            return;
+        };
+        let texcoord_0 = match primitive.attributes.texcoord_0 {
+            Some(texcoord_0) => texcoord_0,
+            None => position, // dummy.
+        };
+        let texcoord_1 = match primitive.attributes.texcoord_1 {
+            Some(texcoord_1) => texcoord_1,
+            None => position, // dummy.
+        };
+        let vertex_count = glb.accessors[position].count as u32;
+        // the primitives without indices draw their vertices in order.
+        let indices = match primitive.indices {
+            Some(indices) => {
+                let index_fmt = match glb.accessors[indices].component_type {
+                    5123 => wgpu::IndexFormat::Uint16,
+                    5125 => wgpu::IndexFormat::Uint32,
+                    _ => return,
+                };
+                let Some(slice) = slice(indices) else { return };
+                Some((slice, index_fmt, glb.accessors[indices].count as u32))
+            }
+            None => None,
+        };
+        let (Some(position), Some(normal), Some(texcoord_0), Some(texcoord_1)) =
+            (slice(position), slice(normal), slice(texcoord_0), slice(texcoord_1))
+        else {
+            return;
+        };
+        pass.set_vertex_buffer(0, position);
+        pass.set_vertex_buffer(1, normal);
+        pass.set_vertex_buffer(2, texcoord_0);
+        pass.set_vertex_buffer(3, texcoord_1);
+        pass.set_vertex_buffer(5, skin);
+        match (head_less, &primitive.head_less_indices, indices) {
+            (Some(Some(buffer)), Some(head_less_indices), _) => {
+                pass.set_index_buffer(buffer.slice(..), wgpu::IndexFormat::Uint32);
+                pass.draw_indexed(0..head_less_indices.len() as u32, 0, instances);
+            }
+            // all the triangles are erased.
+            (Some(None), Some(_), _) => (),
+            (_, _, Some((indices, index_fmt, index_count))) => {
+                pass.set_index_buffer(indices, index_fmt);
+                pass.draw_indexed(0..index_count, 0, instances);
+            }
+            (_, _, None) => pass.draw(0..vertex_count, instances),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texture(index: usize) -> Option<scene::Texture> {
+        Some(scene::Texture {
+            wrap_s: true,
+            wrap_t: true,
+            texcoord: 0,
+            transform: scene::TextureTransform {
+                offset: [0.0, 0.0],
+                rotation: 0.0,
+                scale: [1.0, 1.0],
+            },
+            index: index,
+        })
     }
 
-    pub fn draw_mesh<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, glb: &scene::Glb, mesh: usize, material_id: u32) {
-        let blob = self.blob.as_ref().unwrap();
-        for primitive in glb.meshes[mesh].primitives.iter() {
-            let Some(position) = primitive.attributes.position else {
-                continue;
-            };
-            let Some(normal) = primitive.attributes.normal else {
-                continue;
-            };
-            let texcoord_0 = match primitive.attributes.texcoord_0 {
-                Some(texcoord_0) => texcoord_0,
-                None => position, // dummy.
-            };
-            let texcoord_1 = match primitive.attributes.texcoord_1 {
-                Some(texcoord_1) => texcoord_1,
-                None => position, // dummy.
-            };
-            let Some(indices) = primitive.indices else { continue };
-            let index_fmt = match glb.accessors[indices].component_type {
-                5123 => wgpu::IndexFormat::Uint16,
-                5125 => wgpu::IndexFormat::Uint32,
-                _ => continue,
-            };
-            let Some(material) = primitive.material else { continue };
-            pass.set_bind_group(material_id, &self.materials[material].0, &[]);
-            pass.set_vertex_buffer(0, blob.slice(glb.accessors[position].offset as u64..));
-            pass.set_vertex_buffer(1, blob.slice(glb.accessors[normal].offset as u64..));
-            pass.set_vertex_buffer(2, blob.slice(glb.accessors[texcoord_0].offset as u64..));
-            pass.set_vertex_buffer(3, blob.slice(glb.accessors[texcoord_1].offset as u64..));
-            pass.set_index_buffer(blob.slice(glb.accessors[indices].offset as u64..), index_fmt);
-            pass.draw_indexed(0..glb.accessors[indices].count as u32, 0, 0..1);
+    #[test]
+    fn views_shared_texture_per_slot() {
+        // one image used as the base color and as the normal map.
+        let material = scene::Material {
+            base_color_factor: [1.0; 4],
+            base_color_texture: texture(0),
+            emissive_factor: [0.0; 3],
+            emissive_texture: None,
+            metallic_roughness_texture: None,
+            normal_texture: texture(0),
+            occlusion_texture: None,
+        };
+        for format in [wgpu::TextureFormat::Rgba8Unorm, wgpu::TextureFormat::Rgba8UnormSrgb] {
+            let views = material
+                .textures()
+                .map(|(t, color_space)| (t.index, view_format(format, color_space)))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                views,
+                [
+                    (0, wgpu::TextureFormat::Rgba8UnormSrgb),
+                    (0, wgpu::TextureFormat::Rgba8Unorm)
+                ]
+            );
         }
+        assert_eq!(
+            view_format(wgpu::TextureFormat::Bc7RgbaUnormSrgb, scene::ColorSpace::Linear),
+            wgpu::TextureFormat::Bc7RgbaUnorm
+        );
     }
 }
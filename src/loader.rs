@@ -22,6 +22,15 @@ fn get_vec32f<const N: usize>(json: &tinyjson::JsonValue) -> Option<[f32; N]> {
     Some(dst)
 }
 
+fn get_vecf(json: &tinyjson::JsonValue) -> Option<Vec<f32>> {
+    let json: &Vec<_> = json.get()?;
+    json.iter().map(|e| Some(*e.get::<f64>()? as f32)).collect()
+}
+
+fn get_extension<'a>(json: &'a HashMap<String, tinyjson::JsonValue>, name: &str) -> Option<&'a tinyjson::JsonValue> {
+    json.get("extensions")?.get::<HashMap<_, _>>()?.get(name)
+}
+
 fn load_attributes(json_attributes: &tinyjson::JsonValue) -> Option<scene::Attributes> {
     let json_attributes: &HashMap<_, _> = json_attributes.get()?;
     let position = match json_attributes.get("POSITION") {
@@ -40,15 +49,787 @@ fn load_attributes(json_attributes: &tinyjson::JsonValue) -> Option<scene::Attri
         Some(e) => Some(get_usize(e)?),
         None => None,
     };
+    let joints_0 = match json_attributes.get("JOINTS_0") {
+        Some(e) => Some(get_usize(e)?),
+        None => None,
+    };
+    let weights_0 = match json_attributes.get("WEIGHTS_0") {
+        Some(e) => Some(get_usize(e)?),
+        None => None,
+    };
     Some(scene::Attributes {
         position: position,
         normal: normal,
         texcoord_0: texcoord_0,
         texcoord_1: texcoord_1,
+        joints_0: joints_0,
+        weights_0: weights_0,
+    })
+}
+
+// a texture index out of range only drops the slot, with a warning.
+fn load_texture(json_texture: &tinyjson::JsonValue, textures: &[Vec<usize>]) -> Option<Option<scene::Texture>> {
+    let json_texture: &HashMap<_, _> = json_texture.get()?;
+    let index = get_usize(json_texture.get("index")?)?;
+    let mut texcoord = match json_texture.get("texCoord") {
+        Some(e) => get_usize(e)?,
+        None => 0,
+    };
+    let mut transform = scene::TextureTransform::default();
+    if let Some(e) = get_extension(json_texture, "KHR_texture_transform") {
+        let e: &HashMap<_, _> = e.get()?;
+        if let Some(e) = e.get("offset") {
+            transform.offset = get_vec32f(e)?;
+        }
+        if let Some(e) = e.get("rotation") {
+            transform.rotation = *e.get::<f64>()? as f32;
+        }
+        if let Some(e) = e.get("scale") {
+            transform.scale = get_vec32f(e)?;
+        }
+        if let Some(e) = e.get("texCoord") {
+            texcoord = get_usize(e)?;
+        }
+    }
+    if index >= textures.len() {
+        eprintln!("texture {} does not exist, the material slot is ignored.", index);
+        return Some(None);
+    }
+    Some(Some(scene::Texture {
+        // XXX
+        wrap_s: true,
+        wrap_t: true,
+        texcoord: texcoord,
+        transform: transform,
+        index: index,
+    }))
+}
+
+fn get_f32(json: &HashMap<String, tinyjson::JsonValue>, name: &str, default: f32) -> Option<f32> {
+    match json.get(name) {
+        Some(e) => Some(*e.get::<f64>()? as f32),
+        None => Some(default),
+    }
+}
+
+// VRM 0.x writes vectors as objects.
+fn get_xyz(json: &tinyjson::JsonValue) -> Option<Vector3<f32>> {
+    let json: &HashMap<_, _> = json.get()?;
+    Some(Vector3::new(
+        get_f32(json, "x", 0.0)?,
+        get_f32(json, "y", 0.0)?,
+        get_f32(json, "z", 0.0)?,
+    ))
+}
+
+fn load_springs_1(json_spring_bone: &tinyjson::JsonValue) -> Option<Vec<spring::Spring>> {
+    let json_spring_bone: &HashMap<_, _> = json_spring_bone.get()?;
+    let mut colliders = Vec::new();
+    if let Some(json_colliders) = json_spring_bone.get("colliders") {
+        for json_collider in json_colliders.get::<Vec<_>>()? {
+            let json_collider: &HashMap<_, _> = json_collider.get()?;
+            let node = get_usize(json_collider.get("node")?)?;
+            let json_shape: &HashMap<_, _> = json_collider.get("shape")?.get()?;
+            let offset = |json: &HashMap<_, _>, name| match json.get(name) {
+                Some(e) => Some(Vector3::from(get_vec32f(e)?)),
+                None => Some(Vector3::zeros()),
+            };
+            let shape = if let Some(e) = json_shape.get("sphere") {
+                let e: &HashMap<_, _> = e.get()?;
+                spring::Shape::Sphere {
+                    offset: offset(e, "offset")?,
+                    radius: get_f32(e, "radius", 0.0)?,
+                }
+            } else if let Some(e) = json_shape.get("capsule") {
+                let e: &HashMap<_, _> = e.get()?;
+                spring::Shape::Capsule {
+                    offset: offset(e, "offset")?,
+                    radius: get_f32(e, "radius", 0.0)?,
+                    tail: offset(e, "tail")?,
+                }
+            } else {
+                return None;
+            };
+            colliders.push(spring::Collider {
+                node: node,
+                shape: shape,
+            });
+        }
+    }
+
+    let mut groups = Vec::new();
+    if let Some(json_groups) = json_spring_bone.get("colliderGroups") {
+        for json_group in json_groups.get::<Vec<_>>()? {
+            let json_group: &HashMap<_, _> = json_group.get()?;
+            let mut group = Vec::new();
+            for e in json_group.get("colliders")?.get::<Vec<_>>()? {
+                group.push(colliders.get(get_usize(e)?)?.clone());
+            }
+            groups.push(group);
+        }
+    }
+
+    let mut springs = Vec::new();
+    if let Some(json_springs) = json_spring_bone.get("springs") {
+        for json_spring in json_springs.get::<Vec<_>>()? {
+            let json_spring: &HashMap<_, _> = json_spring.get()?;
+            let mut joints = Vec::new();
+            for json_joint in json_spring.get("joints")?.get::<Vec<_>>()? {
+                let json_joint: &HashMap<_, _> = json_joint.get()?;
+                let gravity_dir = match json_joint.get("gravityDir") {
+                    Some(e) => Vector3::from(get_vec32f(e)?),
+                    None => -Vector3::y(),
+                };
+                joints.push(spring::Joint {
+                    node: get_usize(json_joint.get("node")?)?,
+                    tail: spring::Tail::Extend(0.0),
+                    hit_radius: get_f32(json_joint, "hitRadius", 0.0)?,
+                    stiffness: get_f32(json_joint, "stiffness", 1.0)?,
+                    gravity_power: get_f32(json_joint, "gravityPower", 0.0)?,
+                    gravity_dir: gravity_dir,
+                    drag_force: get_f32(json_joint, "dragForce", 0.5)?,
+                });
+            }
+            // each joint points at the next one. the last joint only serves as the tail.
+            for i in 1..joints.len() {
+                joints[i - 1].tail = spring::Tail::Node(joints[i].node);
+            }
+            joints.pop();
+            let mut colliders = Vec::new();
+            if let Some(e) = json_spring.get("colliderGroups") {
+                for e in e.get::<Vec<_>>()? {
+                    colliders.extend(groups.get(get_usize(e)?)?.iter().cloned());
+                }
+            }
+            let center = match json_spring.get("center") {
+                Some(e) => Some(get_usize(e)?),
+                None => None,
+            };
+            springs.push(spring::Spring {
+                joints: joints,
+                colliders: colliders,
+                center: center,
+            });
+        }
+    }
+    Some(springs)
+}
+
+// VRM 0.x lists the roots of the chains, and every node below them is a joint. the coordinates are those of
+// Unity, so z is flipped.
+fn load_springs_0(json_secondary: &tinyjson::JsonValue, nodes: &[scene::Node]) -> Option<Vec<spring::Spring>> {
+    let json_secondary: &HashMap<_, _> = json_secondary.get()?;
+    let flip = |v: Vector3<f32>| Vector3::new(v[0], v[1], -v[2]);
+
+    let mut groups = Vec::new();
+    if let Some(json_groups) = json_secondary.get("colliderGroups") {
+        for json_group in json_groups.get::<Vec<_>>()? {
+            let json_group: &HashMap<_, _> = json_group.get()?;
+            let node = get_usize(json_group.get("node")?)?;
+            let mut group = Vec::new();
+            for json_collider in json_group.get("colliders")?.get::<Vec<_>>()? {
+                let json_collider: &HashMap<_, _> = json_collider.get()?;
+                let offset = match json_collider.get("offset") {
+                    Some(e) => flip(get_xyz(e)?),
+                    None => Vector3::zeros(),
+                };
+                group.push(spring::Collider {
+                    node: node,
+                    shape: spring::Shape::Sphere {
+                        offset: offset,
+                        radius: get_f32(json_collider, "radius", 0.0)?,
+                    },
+                });
+            }
+            groups.push(group);
+        }
+    }
+
+    let mut springs = Vec::new();
+    if let Some(json_bone_groups) = json_secondary.get("boneGroups") {
+        for json_bone_group in json_bone_groups.get::<Vec<_>>()? {
+            let json_bone_group: &HashMap<_, _> = json_bone_group.get()?;
+            let gravity_dir = match json_bone_group.get("gravityDir") {
+                Some(e) => flip(get_xyz(e)?),
+                None => -Vector3::y(),
+            };
+            let template = spring::Joint {
+                node: 0,
+                tail: spring::Tail::Extend(0.0),
+                hit_radius: get_f32(json_bone_group, "hitRadius", 0.0)?,
+                stiffness: get_f32(json_bone_group, "stiffiness", 1.0)?,
+                gravity_power: get_f32(json_bone_group, "gravityPower", 0.0)?,
+                gravity_dir: gravity_dir,
+                drag_force: get_f32(json_bone_group, "dragForce", 0.5)?,
+            };
+            let mut joints = Vec::new();
+            if let Some(e) = json_bone_group.get("bones") {
+                for e in e.get::<Vec<_>>()? {
+                    let mut stack = vec![get_usize(e)?];
+                    while let Some(n) = stack.pop() {
+                        let children = &nodes.get(n)?.children;
+                        joints.push(spring::Joint {
+                            node: n,
+                            tail: match children.first() {
+                                Some(c) => spring::Tail::Node(*c),
+                                None => spring::Tail::Extend(0.07),
+                            },
+                            ..template.clone()
+                        });
+                        stack.extend(children.iter().rev());
+                    }
+                }
+            }
+            let mut colliders = Vec::new();
+            if let Some(e) = json_bone_group.get("colliderGroups") {
+                for e in e.get::<Vec<_>>()? {
+                    colliders.extend(groups.get(get_usize(e)?)?.iter().cloned());
+                }
+            }
+            // -1 stands for none.
+            let center = match json_bone_group.get("center") {
+                Some(e) if *e.get::<f64>()? >= 0.0 => Some(get_usize(e)?),
+                _ => None,
+            };
+            springs.push(spring::Spring {
+                joints: joints,
+                colliders: colliders,
+                center: center,
+            });
+        }
+    }
+    Some(springs)
+}
+
+fn load_override(json: &HashMap<String, tinyjson::JsonValue>, name: &str) -> Option<expression::Override> {
+    Some(match json.get(name) {
+        Some(e) => match e.get::<String>()?.as_str() {
+            "block" => expression::Override::Block,
+            "blend" => expression::Override::Blend,
+            _ => expression::Override::None,
+        },
+        None => expression::Override::None,
+    })
+}
+
+fn load_expression_1(
+    name: &str,
+    preset: bool,
+    json: &tinyjson::JsonValue,
+    nodes: &[scene::Node],
+) -> Option<expression::Expression> {
+    let json: &HashMap<_, _> = json.get()?;
+    let mut morph_target_binds = Vec::new();
+    if let Some(e) = json.get("morphTargetBinds") {
+        for json_bind in e.get::<Vec<_>>()? {
+            let json_bind: &HashMap<_, _> = json_bind.get()?;
+            let scene::Element::Mesh(mesh) = nodes.get(get_usize(json_bind.get("node")?)?)?.element else {
+                continue;
+            };
+            morph_target_binds.push(expression::MorphTargetBind {
+                mesh: mesh,
+                index: get_usize(json_bind.get("index")?)?,
+                weight: get_f32(json_bind, "weight", 0.0)?,
+            });
+        }
+    }
+    let mut material_color_binds = Vec::new();
+    if let Some(e) = json.get("materialColorBinds") {
+        for json_bind in e.get::<Vec<_>>()? {
+            let json_bind: &HashMap<_, _> = json_bind.get()?;
+            let color_type = match json_bind.get("type")?.get::<String>()?.as_str() {
+                "color" => expression::ColorType::Color,
+                "emissionColor" => expression::ColorType::EmissionColor,
+                "shadeColor" => expression::ColorType::ShadeColor,
+                "matcapColor" => expression::ColorType::MatcapColor,
+                "rimColor" => expression::ColorType::RimColor,
+                "outlineColor" => expression::ColorType::OutlineColor,
+                _ => return None,
+            };
+            material_color_binds.push(expression::MaterialColorBind {
+                material: get_usize(json_bind.get("material")?)?,
+                color_type: color_type,
+                target: get_vec32f(json_bind.get("targetValue")?)?,
+            });
+        }
+    }
+    let mut texture_transform_binds = Vec::new();
+    if let Some(e) = json.get("textureTransformBinds") {
+        for json_bind in e.get::<Vec<_>>()? {
+            let json_bind: &HashMap<_, _> = json_bind.get()?;
+            texture_transform_binds.push(expression::TextureTransformBind {
+                material: get_usize(json_bind.get("material")?)?,
+                scale: match json_bind.get("scale") {
+                    Some(e) => get_vec32f(e)?,
+                    None => [1.0, 1.0],
+                },
+                offset: match json_bind.get("offset") {
+                    Some(e) => get_vec32f(e)?,
+                    None => [0.0, 0.0],
+                },
+            });
+        }
+    }
+    Some(expression::Expression {
+        name: name.to_string(),
+        preset: preset,
+        is_binary: matches!(json.get("isBinary").and_then(|e| e.get::<bool>()), Some(true)),
+        override_blink: load_override(json, "overrideBlink")?,
+        override_look_at: load_override(json, "overrideLookAt")?,
+        override_mouth: load_override(json, "overrideMouth")?,
+        morph_target_binds: morph_target_binds,
+        material_color_binds: material_color_binds,
+        texture_transform_binds: texture_transform_binds,
     })
 }
 
-fn load_root(json_root: &tinyjson::JsonValue, blob: Vec<u8>) -> Option<scene::Glb> {
+fn load_expressions_1(
+    json_expressions: &tinyjson::JsonValue,
+    nodes: &[scene::Node],
+) -> Option<Vec<expression::Expression>> {
+    let json_expressions: &HashMap<_, _> = json_expressions.get()?;
+    let mut expressions = Vec::new();
+    for (key, preset) in [("preset", true), ("custom", false)] {
+        if let Some(e) = json_expressions.get(key) {
+            let e: &HashMap<_, _> = e.get()?;
+            let mut names = e.keys().collect::<Vec<_>>();
+            names.sort();
+            for name in names {
+                expressions.push(load_expression_1(name, preset, &e[name], nodes)?);
+            }
+        }
+    }
+    Some(expressions)
+}
+
+// VRM 0.x refers to the materials by name and to their properties by the MToon names. the presets are renamed to
+// those of VRM 1.0.
+fn load_expressions_0(
+    json_master: &tinyjson::JsonValue,
+    material_names: &[String],
+) -> Option<Vec<expression::Expression>> {
+    let json_master: &HashMap<_, _> = json_master.get()?;
+    let mut expressions = Vec::new();
+    let Some(json_groups) = json_master.get("blendShapeGroups") else {
+        return Some(expressions);
+    };
+    for json_group in json_groups.get::<Vec<_>>()? {
+        let json_group: &HashMap<_, _> = json_group.get()?;
+        let group_name = match json_group.get("name") {
+            Some(e) => e.get::<String>()?.as_str(),
+            None => "",
+        };
+        let preset = match json_group.get("presetName") {
+            Some(e) => match e.get::<String>()?.as_str() {
+                "a" => "aa",
+                "i" => "ih",
+                "u" => "ou",
+                "e" => "ee",
+                "o" => "oh",
+                "joy" => "happy",
+                "sorrow" => "sad",
+                "fun" => "relaxed",
+                "blink_l" => "blinkLeft",
+                "blink_r" => "blinkRight",
+                "lookup" => "lookUp",
+                "lookdown" => "lookDown",
+                "lookleft" => "lookLeft",
+                "lookright" => "lookRight",
+                name => name,
+            },
+            None => "unknown",
+        };
+        let (name, preset) = match expression::Expression::PRESETS.contains(&preset) {
+            true => (preset, true),
+            false => (group_name, false),
+        };
+
+        let mut morph_target_binds = Vec::new();
+        if let Some(e) = json_group.get("binds") {
+            for json_bind in e.get::<Vec<_>>()? {
+                let json_bind: &HashMap<_, _> = json_bind.get()?;
+                morph_target_binds.push(expression::MorphTargetBind {
+                    mesh: get_usize(json_bind.get("mesh")?)?,
+                    index: get_usize(json_bind.get("index")?)?,
+                    weight: get_f32(json_bind, "weight", 0.0)? / 100.0,
+                });
+            }
+        }
+        let mut material_color_binds = Vec::new();
+        let mut texture_transform_binds = Vec::new();
+        if let Some(e) = json_group.get("materialValues") {
+            for json_value in e.get::<Vec<_>>()? {
+                let json_value: &HashMap<_, _> = json_value.get()?;
+                let material_name = json_value.get("materialName")?.get::<String>()?;
+                let Some(material) = material_names.iter().position(|n| n == material_name) else {
+                    continue;
+                };
+                let value = get_vecf(json_value.get("targetValue")?)?;
+                let [x, y, z, w] = value.get(..4)?.try_into().unwrap();
+                let color_type = match json_value.get("propertyName")?.get::<String>()?.as_str() {
+                    "_Color" => expression::ColorType::Color,
+                    "_EmissionColor" => expression::ColorType::EmissionColor,
+                    "_ShadeColor" => expression::ColorType::ShadeColor,
+                    "_RimColor" => expression::ColorType::RimColor,
+                    "_OutlineColor" => expression::ColorType::OutlineColor,
+                    // unity applies the scale and offset with v pointing up.
+                    "_MainTex_ST" => {
+                        texture_transform_binds.push(expression::TextureTransformBind {
+                            material: material,
+                            scale: [x, y],
+                            offset: [z, 1.0 - w - y],
+                        });
+                        continue;
+                    }
+                    _ => continue,
+                };
+                material_color_binds.push(expression::MaterialColorBind {
+                    material: material,
+                    color_type: color_type,
+                    target: [x, y, z, w],
+                });
+            }
+        }
+        expressions.push(expression::Expression {
+            name: name.to_string(),
+            preset: preset,
+            is_binary: matches!(json_group.get("isBinary").and_then(|e| e.get::<bool>()), Some(true)),
+            override_blink: expression::Override::None,
+            override_look_at: expression::Override::None,
+            override_mouth: expression::Override::None,
+            morph_target_binds: morph_target_binds,
+            material_color_binds: material_color_binds,
+            texture_transform_binds: texture_transform_binds,
+        });
+    }
+    Some(expressions)
+}
+
+fn load_humanoid_1(json_humanoid: &tinyjson::JsonValue) -> Option<HashMap<String, usize>> {
+    let json_bones: &HashMap<_, _> = json_humanoid.get::<HashMap<_, _>>()?.get("humanBones")?.get()?;
+    let mut humanoid = HashMap::new();
+    for (name, e) in json_bones.iter() {
+        humanoid.insert(name.clone(), get_usize(e.get::<HashMap<_, _>>()?.get("node")?)?);
+    }
+    Some(humanoid)
+}
+
+// the thumbs have been renamed in VRM 1.0.
+fn load_humanoid_0(json_humanoid: &tinyjson::JsonValue) -> Option<HashMap<String, usize>> {
+    let json_bones: &Vec<_> = json_humanoid.get::<HashMap<_, _>>()?.get("humanBones")?.get()?;
+    let mut humanoid = HashMap::new();
+    for json_bone in json_bones.iter() {
+        let json_bone: &HashMap<_, _> = json_bone.get()?;
+        let name = json_bone.get("bone")?.get::<String>()?;
+        let name = match name.strip_suffix("ThumbProximal") {
+            Some(side) => format!("{}ThumbMetacarpal", side),
+            None => match name.strip_suffix("ThumbIntermediate") {
+                Some(side) => format!("{}ThumbProximal", side),
+                None => name.clone(),
+            },
+        };
+        humanoid.insert(name, get_usize(json_bone.get("node")?)?);
+    }
+    Some(humanoid)
+}
+
+fn load_look_at_1(json_look_at: &tinyjson::JsonValue) -> Option<look_at::LookAt> {
+    let json_look_at: &HashMap<_, _> = json_look_at.get()?;
+    let range_map = |name| -> Option<look_at::RangeMap> {
+        let json: &HashMap<_, _> = match json_look_at.get(name) {
+            Some(e) => e.get()?,
+            None => &HashMap::new(),
+        };
+        Some(look_at::RangeMap {
+            input_max: get_f32(json, "inputMaxValue", 90.0)?,
+            output_scale: get_f32(json, "outputScale", 10.0)?,
+        })
+    };
+    Some(look_at::LookAt {
+        offset: match json_look_at.get("offsetFromHeadBone") {
+            Some(e) => Vector3::from(get_vec32f(e)?),
+            None => Vector3::zeros(),
+        },
+        kind: match json_look_at
+            .get("type")
+            .and_then(|e| e.get::<String>())
+            .map(|e| e.as_str())
+        {
+            Some("expression") => look_at::Kind::Expression,
+            _ => look_at::Kind::Bone,
+        },
+        horizontal_inner: range_map("rangeMapHorizontalInner")?,
+        horizontal_outer: range_map("rangeMapHorizontalOuter")?,
+        vertical_down: range_map("rangeMapVerticalDown")?,
+        vertical_up: range_map("rangeMapVerticalUp")?,
+        facing: UnitQuaternion::identity(),
+    })
+}
+
+// VRM 0.x keeps the settings with the first person ones. the curves are approximated by their ranges.
+fn load_look_at_0(json_first_person: &tinyjson::JsonValue) -> Option<look_at::LookAt> {
+    let json_first_person: &HashMap<_, _> = json_first_person.get()?;
+    let range_map = |name| -> Option<look_at::RangeMap> {
+        let json: &HashMap<_, _> = match json_first_person.get(name) {
+            Some(e) => e.get()?,
+            None => &HashMap::new(),
+        };
+        Some(look_at::RangeMap {
+            input_max: get_f32(json, "xRange", 90.0)?,
+            output_scale: get_f32(json, "yRange", 10.0)?,
+        })
+    };
+    let offset = match json_first_person.get("firstPersonBoneOffset") {
+        Some(e) => get_xyz(e)?,
+        None => Vector3::zeros(),
+    };
+    Some(look_at::LookAt {
+        offset: Vector3::new(offset[0], offset[1], -offset[2]),
+        kind: match json_first_person
+            .get("lookAtTypeName")
+            .and_then(|e| e.get::<String>())
+            .map(|e| e.as_str())
+        {
+            Some("BlendShape") => look_at::Kind::Expression,
+            _ => look_at::Kind::Bone,
+        },
+        horizontal_inner: range_map("lookAtHorizontalInner")?,
+        horizontal_outer: range_map("lookAtHorizontalOuter")?,
+        vertical_down: range_map("lookAtVerticalDown")?,
+        vertical_up: range_map("lookAtVerticalUp")?,
+        facing: UnitQuaternion::from_axis_angle(&Vector3::y_axis(), f32::consts::PI),
+    })
+}
+
+fn load_first_person_type(name: &str) -> Option<scene::FirstPerson> {
+    Some(match name {
+        "auto" | "Auto" => scene::FirstPerson::Auto,
+        "both" | "Both" => scene::FirstPerson::Both,
+        "thirdPersonOnly" | "ThirdPersonOnly" => scene::FirstPerson::ThirdPersonOnly,
+        "firstPersonOnly" | "FirstPersonOnly" => scene::FirstPerson::FirstPersonOnly,
+        _ => return None,
+    })
+}
+
+// the meshes which are not annotated are "Auto". VRM 0.x annotates meshes instead of nodes.
+fn load_first_person(json_first_person: &tinyjson::JsonValue, nodes: &mut [scene::Node], vrm_0: bool) -> Option<()> {
+    for node in nodes.iter_mut() {
+        node.first_person = scene::FirstPerson::Auto;
+    }
+    let json_first_person: &HashMap<_, _> = json_first_person.get()?;
+    let Some(json_annotations) = json_first_person.get("meshAnnotations") else {
+        return Some(());
+    };
+    for json_annotation in json_annotations.get::<Vec<_>>()? {
+        let json_annotation: &HashMap<_, _> = json_annotation.get()?;
+        if vrm_0 {
+            let Some(mesh) = json_annotation.get("mesh").and_then(get_usize) else {
+                continue;
+            };
+            let first_person = load_first_person_type(json_annotation.get("firstPersonFlag")?.get::<String>()?)?;
+            for node in nodes.iter_mut() {
+                if matches!(node.element, scene::Element::Mesh(m) if m == mesh) {
+                    node.first_person = first_person;
+                }
+            }
+        } else {
+            let node = nodes.get_mut(get_usize(json_annotation.get("node")?)?)?;
+            node.first_person = load_first_person_type(json_annotation.get("type")?.get::<String>()?)?;
+        }
+    }
+    Some(())
+}
+
+// the triangles without a vertex weighted to the erased joints.
+fn head_less_triangles(indices: &[f32], joints: &[f32], weights: &[f32], erased: &[bool]) -> Vec<u32> {
+    let erased_vertex = |v: f32| {
+        let v = v as usize * 4;
+        (v..v + 4).any(|k| {
+            weights.get(k).is_some_and(|w| *w > 0.0)
+                && joints.get(k).is_some_and(|j| erased.get(*j as usize) == Some(&true))
+        })
+    };
+    indices
+        .chunks_exact(3)
+        .filter(|t| !t.iter().any(|v| erased_vertex(*v)))
+        .flatten()
+        .map(|v| *v as u32)
+        .collect()
+}
+
+// resolves "Auto". the meshes without a skin are hidden in the first person view if they hang from the head, and
+// the skinned ones get a variant without the triangles weighted to the head or below.
+fn build_head_less(glb: &mut scene::Glb) {
+    let mut head = vec![false; glb.nodes.len()];
+    if let Some(n) = glb.humanoid.get("head") {
+        let mut stack = vec![*n];
+        while let Some(n) = stack.pop() {
+            head[n] = true;
+            stack.extend(glb.nodes[n].children.iter());
+        }
+    }
+    let blob = glb.blob.as_ref().unwrap();
+    for i in 0..glb.nodes.len() {
+        let node = &glb.nodes[i];
+        let (scene::FirstPerson::Auto, scene::Element::Mesh(mesh)) = (node.first_person, &node.element) else {
+            continue;
+        };
+        let Some(skin) = node.skin else {
+            glb.nodes[i].first_person = match head[i] {
+                true => scene::FirstPerson::ThirdPersonOnly,
+                false => scene::FirstPerson::Both,
+            };
+            continue;
+        };
+        let erased = glb.skins[skin].joints.iter().map(|j| head[*j]).collect::<Vec<_>>();
+        let mesh = *mesh;
+        for p in 0..glb.meshes[mesh].primitives.len() {
+            let primitive = &glb.meshes[mesh].primitives[p];
+            if primitive.head_less_indices.is_some() {
+                continue;
+            }
+            let read = |index: Option<usize>, normalized| {
+                let accessor = &glb.accessors[index?];
+                Some(accessor.read(&glb.views[accessor.view], blob, normalized))
+            };
+            // the primitives without indices draw their vertices in order.
+            let indices = match primitive.indices {
+                Some(_) => read(primitive.indices, false),
+                None => primitive
+                    .attributes
+                    .position
+                    .map(|p| (0..glb.accessors[p].count).map(|i| i as f32).collect()),
+            };
+            let (Some(indices), Some(joints), Some(weights)) = (
+                indices,
+                read(primitive.attributes.joints_0, false),
+                read(primitive.attributes.weights_0, true),
+            ) else {
+                continue;
+            };
+            let head_less = head_less_triangles(&indices, &joints, &weights, &erased);
+            glb.meshes[mesh].primitives[p].head_less_indices = Some(head_less);
+        }
+    }
+}
+
+// the keys are read here, as the blob is released after the upload.
+fn load_animations(
+    json_animations: &tinyjson::JsonValue,
+    accessors: &[scene::Accessor],
+    views: &[scene::View],
+    blob: &[u8],
+) -> Option<Vec<animation::Clip>> {
+    let mut animations = Vec::new();
+    for json_animation in json_animations.get::<Vec<_>>()? {
+        let json_animation: &HashMap<_, _> = json_animation.get()?;
+        let name = match json_animation.get("name") {
+            Some(e) => e.get::<String>()?.clone(),
+            None => String::new(),
+        };
+        let json_samplers = json_animation.get("samplers")?.get::<Vec<_>>()?;
+        let mut channels = Vec::new();
+        for json_channel in json_animation.get("channels")?.get::<Vec<_>>()? {
+            let json_channel: &HashMap<_, _> = json_channel.get()?;
+            let json_target: &HashMap<_, _> = json_channel.get("target")?.get()?;
+            // channels without a node are meant for extensions.
+            let Some(node) = json_target.get("node") else {
+                continue;
+            };
+            let (path, width) = match json_target.get("path")?.get::<String>()?.as_str() {
+                "translation" => (animation::Path::Translation, Some(3)),
+                "rotation" => (animation::Path::Rotation, Some(4)),
+                "scale" => (animation::Path::Scale, Some(3)),
+                "weights" => (animation::Path::Weights, None),
+                _ => continue,
+            };
+            let json_sampler: &HashMap<_, _> = json_samplers.get(get_usize(json_channel.get("sampler")?)?)?.get()?;
+            let interpolation = match json_sampler.get("interpolation") {
+                Some(e) => match e.get::<String>()?.as_str() {
+                    "STEP" => animation::Interpolation::Step,
+                    "LINEAR" => animation::Interpolation::Linear,
+                    "CUBICSPLINE" => animation::Interpolation::CubicSpline,
+                    _ => return None,
+                },
+                None => animation::Interpolation::Linear,
+            };
+            let input = accessors.get(get_usize(json_sampler.get("input")?)?)?;
+            let output = accessors.get(get_usize(json_sampler.get("output")?)?)?;
+            if input.component_count != 1 || width.is_some_and(|n| n != output.component_count) {
+                return None;
+            }
+            let times = input.read(&views[input.view], blob, false);
+            let values = output.read(&views[output.view], blob, true);
+            let keys = match interpolation {
+                animation::Interpolation::CubicSpline => times.len() * 3,
+                _ => times.len(),
+            };
+            if times.is_empty()
+                || values.len() % keys != 0
+                || width.is_some_and(|n| values.len() != keys * n)
+                || times.windows(2).any(|t| t[0] > t[1])
+            {
+                return None;
+            }
+            channels.push(animation::Channel {
+                node: get_usize(node)?,
+                path: path,
+                interpolation: interpolation,
+                times: times,
+                values: values,
+            });
+        }
+        animations.push(animation::Clip {
+            name: name,
+            channels: channels,
+            expressions: Vec::new(),
+            look_at: None,
+        });
+    }
+    Some(animations)
+}
+
+// VRMC_vrm_animation drives the expressions with the x translation of nodes and lookAt with the rotation of a node.
+fn load_vrm_animation(json_vrm_animation: &tinyjson::JsonValue, animations: &mut [animation::Clip]) -> Option<()> {
+    let json_vrm_animation: &HashMap<_, _> = json_vrm_animation.get()?;
+    let mut expressions = Vec::new();
+    if let Some(json_expressions) = json_vrm_animation.get("expressions") {
+        let json_expressions: &HashMap<_, _> = json_expressions.get()?;
+        for group in ["preset", "custom"] {
+            if let Some(e) = json_expressions.get(group) {
+                for (name, e) in e.get::<HashMap<_, _>>()? {
+                    expressions.push((name.clone(), get_usize(e.get::<HashMap<_, _>>()?.get("node")?)?));
+                }
+            }
+        }
+    }
+    expressions.sort();
+    let look_at = match json_vrm_animation.get("lookAt") {
+        Some(e) => Some(get_usize(e.get::<HashMap<_, _>>()?.get("node")?)?),
+        None => None,
+    };
+    for clip in animations.iter_mut() {
+        let find = |node: usize, path| clip.channels.iter().find(|c| c.node == node && c.path == path);
+        let bound = expressions
+            .iter()
+            .filter_map(|(name, node)| {
+                let channel = find(*node, animation::Path::Translation)?;
+                let channel = animation::Channel {
+                    path: animation::Path::Weights,
+                    values: channel.values.iter().step_by(3).copied().collect(),
+                    ..channel.clone()
+                };
+                Some((name.clone(), channel))
+            })
+            .collect();
+        let look_at = look_at.and_then(|n| find(n, animation::Path::Rotation).cloned());
+        clip.expressions = bound;
+        clip.look_at = look_at;
+    }
+    Some(())
+}
+
+fn load_root(json_root: &tinyjson::JsonValue, blob: scene::Blob) -> Option<scene::Glb> {
     let json_root: &HashMap<_, _> = json_root.get()?;
 
     let mut views = Vec::new();
@@ -66,8 +847,20 @@ fn load_root(json_root: &tinyjson::JsonValue, blob: Vec<u8>) -> Option<scene::Gl
             Some(e) => Some(get_usize(e)?),
             None => None,
         };
+        let target = match json_views.get("target") {
+            Some(e) => Some(get_usize(e)?),
+            None => None,
+        };
+        if offset + length > blob.len() {
+            return None;
+        }
 
-        views.push((offset, length, stride));
+        views.push(scene::View {
+            offset: offset,
+            length: length,
+            stride: stride,
+            target: target,
+        });
     }
 
     let mut accessors = Vec::new();
@@ -88,17 +881,37 @@ fn load_root(json_root: &tinyjson::JsonValue, blob: Vec<u8>) -> Option<scene::Gl
             "MAT4" => 16,
             _ => return None,
         };
-        accessors.push(scene::Accessor {
-            offset: views[view].0 + offset,
+        let min = match json_accessors.get("min") {
+            Some(e) => get_vecf(e)?,
+            None => Vec::new(),
+        };
+        let max = match json_accessors.get("max") {
+            Some(e) => get_vecf(e)?,
+            None => Vec::new(),
+        };
+        let accessor = scene::Accessor {
+            view: view,
+            offset: offset,
             count: count,
-            stride: views[view].2,
+            stride: views.get(view)?.stride,
             component_type: component_type,
             component_count: component_count,
-        });
+            min: min,
+            max: max,
+        };
+        if offset + accessor.byte_length() > views[view].length {
+            return None;
+        }
+        accessors.push(accessor);
     }
 
+    // the meshes are optional, as in files which only hold animations.
     let mut meshes = Vec::new();
-    for json_mesh in json_root.get("meshes")?.get::<Vec<_>>()? {
+    let json_meshes = match json_root.get("meshes") {
+        Some(e) => e.get::<Vec<_>>()?.as_slice(),
+        None => &[],
+    };
+    for json_mesh in json_meshes {
         let json_mesh: &HashMap<_, _> = json_mesh.get()?;
         let mut primitives = Vec::new();
         for json_primitive in json_mesh.get("primitives")?.get::<Vec<_>>()? {
@@ -127,6 +940,7 @@ fn load_root(json_root: &tinyjson::JsonValue, blob: Vec<u8>) -> Option<scene::Gl
                 targets: targets,
                 indices: indices,
                 material: material,
+                head_less_indices: None,
             });
         }
         let weights = match json_mesh.get("weights") {
@@ -178,6 +992,10 @@ fn load_root(json_root: &tinyjson::JsonValue, blob: Vec<u8>) -> Option<scene::Gl
             Some(e) => scene::Element::Mesh(get_usize(e)?),
             None => scene::Element::None,
         };
+        let skin = match json_node.get("skin") {
+            Some(e) => Some(get_usize(e)?),
+            None => None,
+        };
         nodes.push(scene::Node {
             name: name.to_string(),
             children: children,
@@ -185,6 +1003,8 @@ fn load_root(json_root: &tinyjson::JsonValue, blob: Vec<u8>) -> Option<scene::Gl
             rotation: UnitQuaternion::from_quaternion(Quaternion::from(rotation)),
             scale: Vector3::from(scale),
             element: element,
+            skin: skin,
+            first_person: scene::FirstPerson::Both,
         });
     }
 
@@ -196,17 +1016,80 @@ fn load_root(json_root: &tinyjson::JsonValue, blob: Vec<u8>) -> Option<scene::Gl
         }
     }
 
+    // the inverse bind matrices are read here, as the blob is released after the upload.
+    let mut skins = Vec::new();
+    if let Some(json_skins) = json_root.get("skins") {
+        for json_skin in json_skins.get::<Vec<_>>()? {
+            let json_skin: &HashMap<_, _> = json_skin.get()?;
+            let mut joints = Vec::new();
+            for e in json_skin.get("joints")?.get::<Vec<_>>()? {
+                let joint = get_usize(e)?;
+                nodes.get(joint)?;
+                joints.push(joint);
+            }
+            let inverse_bind_matrices = match json_skin.get("inverseBindMatrices") {
+                Some(e) => {
+                    let accessor = accessors.get(get_usize(e)?)?;
+                    if accessor.component_count != 16 || accessor.count < joints.len() {
+                        return None;
+                    }
+                    let data = accessor.read(&views[accessor.view], &blob, false);
+                    data.chunks(16).map(nalgebra::Matrix4::from_column_slice).collect()
+                }
+                None => vec![nalgebra::Matrix4::identity(); joints.len()],
+            };
+            skins.push(scene::Skin {
+                joints: joints,
+                inverse_bind_matrices: inverse_bind_matrices,
+            });
+        }
+    }
+
+    if nodes.iter().filter_map(|n| n.skin).any(|skin| skin >= skins.len()) {
+        return None;
+    }
+
+    let json_vrm_1 = get_extension(json_root, "VRMC_vrm").and_then(|e| e.get::<HashMap<_, _>>());
+    let json_vrm_0 = get_extension(json_root, "VRM").and_then(|e| e.get::<HashMap<_, _>>());
+    let springs = if let Some(e) = get_extension(json_root, "VRMC_springBone") {
+        load_springs_1(e)?
+    } else if let Some(e) = json_vrm_0.and_then(|e| e.get("secondaryAnimation")) {
+        load_springs_0(e, &nodes)?
+    } else {
+        Vec::new()
+    };
+    for spring in springs.iter() {
+        let tails = spring.joints.iter().filter_map(|j| match j.tail {
+            spring::Tail::Node(n) => Some(n),
+            spring::Tail::Extend(_) => None,
+        });
+        let others = spring.colliders.iter().map(|c| c.node).chain(spring.center);
+        if spring
+            .joints
+            .iter()
+            .map(|j| j.node)
+            .chain(tails)
+            .chain(others)
+            .any(|n| n >= nodes.len())
+        {
+            return None;
+        }
+    }
+
     let mut images = Vec::new();
     if let Some(json_images) = json_root.get("images") {
         for json_image in json_images.get::<Vec<_>>()? {
             let json_image: &HashMap<_, _> = json_image.get()?;
             let image = match json_image.get("bufferView") {
                 Some(view) => {
-                    let (offset, length, _) = *views.get(get_usize(view)?)?;
-                    let image = image::load_from_memory(&blob[offset..offset + length]).ok()?;
-                    Some(scene::Image {
-                        dims: [image.width(), image.height(), 4],
-                        buffer: image.into_rgba8().into_vec(),
+                    let view = views.get(get_usize(view)?)?;
+                    let mime_type = match json_image.get("mimeType") {
+                        Some(e) => e.get::<String>()?.clone(),
+                        None => String::new(),
+                    };
+                    Some(scene::ImageSource {
+                        range: view.offset..view.offset + view.length,
+                        mime_type: mime_type,
                     })
                 }
                 None => None,
@@ -220,68 +1103,280 @@ fn load_root(json_root: &tinyjson::JsonValue, blob: Vec<u8>) -> Option<scene::Gl
         for json_texture in json_textures.get::<Vec<_>>()? {
             let json_texture: &HashMap<_, _> = json_texture.get()?;
             // XXX: sampler.
-            let source = get_usize(json_texture.get("source")?)?;
-            textures.push(source);
+            // the extensions point to KTX2 and WebP images, "source" is the optional fallback.
+            let mut sources = Vec::new();
+            for name in ["KHR_texture_basisu", "EXT_texture_webp"] {
+                if let Some(e) = get_extension(json_texture, name) {
+                    sources.push(get_usize(e.get::<HashMap<_, _>>()?.get("source")?)?);
+                }
+            }
+            if let Some(e) = json_texture.get("source") {
+                sources.push(get_usize(e)?);
+            }
+            if sources.is_empty() {
+                return None;
+            }
+            textures.push(sources);
         }
     }
 
     let mut materials = Vec::new();
+    let mut material_names = Vec::new();
     if let Some(json_materials) = json_root.get("materials") {
         for json_material in json_materials.get::<Vec<_>>()? {
             let json_material: &HashMap<_, _> = json_material.get()?;
-            let material = match json_material.get("pbrMetallicRoughness") {
-                Some(json_pbr) => {
-                    let json_pbr: &HashMap<_, _> = json_pbr.get()?;
-                    let base_color_factor = match json_pbr.get("baseColorFactor") {
-                        Some(e) => get_vec32f(e)?,
-                        None => [1.0, 1.0, 1.0, 1.0],
-                    };
-                    let base_color_texture = match json_pbr.get("baseColorTexture") {
-                        Some(e) => {
-                            let e: &HashMap<_, _> = e.get()?;
-                            let index = get_usize(e.get("index")?)?;
-                            let texcoord = match e.get("texCoord") {
-                                Some(e) => get_usize(e)?,
-                                None => 0,
-                            };
-                            Some(scene::Texture {
-                                // XXX
-                                wrap_s: true,
-                                wrap_t: true,
-                                texcoord: texcoord,
-                                image: textures[index],
-                            })
-                        }
-                        None => None,
-                    };
-                    scene::Material {
-                        base_color_factor: base_color_factor,
-                        base_color_texture: base_color_texture,
+            material_names.push(match json_material.get("name") {
+                Some(e) => e.get::<String>()?.clone(),
+                None => String::new(),
+            });
+            let (base_color_factor, base_color_texture, metallic_roughness_texture) =
+                match json_material.get("pbrMetallicRoughness") {
+                    Some(json_pbr) => {
+                        let json_pbr: &HashMap<_, _> = json_pbr.get()?;
+                        let base_color_factor = match json_pbr.get("baseColorFactor") {
+                            Some(e) => get_vec32f(e)?,
+                            None => [1.0, 1.0, 1.0, 1.0],
+                        };
+                        let base_color_texture = match json_pbr.get("baseColorTexture") {
+                            Some(e) => load_texture(e, &textures)?,
+                            None => None,
+                        };
+                        let metallic_roughness_texture = match json_pbr.get("metallicRoughnessTexture") {
+                            Some(e) => load_texture(e, &textures)?,
+                            None => None,
+                        };
+                        (base_color_factor, base_color_texture, metallic_roughness_texture)
                     }
-                }
-                None => scene::Material {
-                    base_color_factor: [1.0, 1.0, 1.0, 1.0],
-                    base_color_texture: None,
-                },
+                    None => ([1.0, 1.0, 1.0, 1.0], None, None),
+                };
+            let emissive_strength = match get_extension(json_material, "KHR_materials_emissive_strength") {
+                Some(e) => *e.get::<HashMap<_, _>>()?.get("emissiveStrength")?.get::<f64>()? as f32,
+                None => 1.0,
+            };
+            let emissive_factor = match json_material.get("emissiveFactor") {
+                Some(e) => get_vec32f(e)?.map(|x| x * emissive_strength),
+                None => [0.0, 0.0, 0.0],
+            };
+            let emissive_texture = match json_material.get("emissiveTexture") {
+                Some(e) => load_texture(e, &textures)?,
+                None => None,
+            };
+            let normal_texture = match json_material.get("normalTexture") {
+                Some(e) => load_texture(e, &textures)?,
+                None => None,
+            };
+            let occlusion_texture = match json_material.get("occlusionTexture") {
+                Some(e) => load_texture(e, &textures)?,
+                None => None,
             };
-            materials.push(material);
+            materials.push(scene::Material {
+                base_color_factor: base_color_factor,
+                base_color_texture: base_color_texture,
+                emissive_factor: emissive_factor,
+                emissive_texture: emissive_texture,
+                metallic_roughness_texture: metallic_roughness_texture,
+                normal_texture: normal_texture,
+                occlusion_texture: occlusion_texture,
+            });
         }
     }
 
-    Some(scene::Glb {
+    let expressions = if let Some(e) = json_vrm_1.and_then(|e| e.get("expressions")) {
+        load_expressions_1(e, &nodes)?
+    } else if let Some(e) = json_vrm_0.and_then(|e| e.get("blendShapeMaster")) {
+        load_expressions_0(e, &material_names)?
+    } else {
+        Vec::new()
+    };
+    for expression in expressions.iter() {
+        let mut bound_meshes = expression.morph_target_binds.iter().map(|b| b.mesh);
+        let mut bound_materials = (expression.material_color_binds.iter().map(|b| b.material))
+            .chain(expression.texture_transform_binds.iter().map(|b| b.material));
+        if bound_meshes.any(|m| m >= meshes.len()) || bound_materials.any(|m| m >= materials.len()) {
+            return None;
+        }
+    }
+    let expressions = expression::Expressions::new(expressions, &meshes, &materials);
+
+    let json_vrm_animation = get_extension(json_root, "VRMC_vrm_animation");
+    let humanoid = if let Some(e) = json_vrm_1.and_then(|e| e.get("humanoid")) {
+        load_humanoid_1(e)?
+    } else if let Some(e) = json_vrm_0.and_then(|e| e.get("humanoid")) {
+        load_humanoid_0(e)?
+    } else if let Some(e) = json_vrm_animation.and_then(|e| e.get::<HashMap<_, _>>()?.get("humanoid")) {
+        load_humanoid_1(e)?
+    } else {
+        HashMap::new()
+    };
+    if humanoid.values().any(|n| *n >= nodes.len()) {
+        return None;
+    }
+    if let Some(e) = json_vrm_1.and_then(|e| e.get("firstPerson")) {
+        load_first_person(e, &mut nodes, false)?;
+    } else if let Some(e) = json_vrm_0.and_then(|e| e.get("firstPerson")) {
+        load_first_person(e, &mut nodes, true)?;
+    }
+    let look_at = if let Some(e) = json_vrm_1.and_then(|e| e.get("lookAt")) {
+        Some(load_look_at_1(e)?)
+    } else if let Some(e) = json_vrm_0.and_then(|e| e.get("firstPerson")) {
+        Some(load_look_at_0(e)?)
+    } else {
+        None
+    };
+
+    let mut animations = match json_root.get("animations") {
+        Some(e) => load_animations(e, &accessors, &views, &blob)?,
+        None => Vec::new(),
+    };
+    if let Some(e) = json_vrm_animation {
+        load_vrm_animation(e, &mut animations)?;
+    }
+    if animations
+        .iter()
+        .flat_map(|a| a.channels.iter())
+        .any(|c| c.node >= nodes.len())
+    {
+        return None;
+    }
+
+    let mut glb = scene::Glb {
         materials: materials,
+        views: views,
         accessors: accessors,
         meshes: meshes,
         nodes: nodes,
         roots: roots,
-        blob: blob,
+        skins: skins,
+        springs: springs,
+        expressions: expressions,
+        humanoid: humanoid,
+        look_at: look_at,
+        animations: animations,
+        blob: Some(blob),
         images: images,
+        textures: textures,
+        geometry: Vec::new(),
+    };
+    build_head_less(&mut glb);
+    glb.geometry = pick::build(&glb, glb.blob.as_ref().unwrap());
+    Some(glb)
+}
+
+fn ktx2_format(format: ktx2::Format) -> Option<wgpu::TextureFormat> {
+    use wgpu::TextureFormat as F;
+    let astc = |channel| F::Astc {
+        block: wgpu::AstcBlock::B4x4,
+        channel: channel,
+    };
+    Some(match format {
+        ktx2::Format::R8G8B8A8_UNORM => F::Rgba8Unorm,
+        ktx2::Format::R8G8B8A8_SRGB => F::Rgba8UnormSrgb,
+        ktx2::Format::BC1_RGBA_UNORM_BLOCK => F::Bc1RgbaUnorm,
+        ktx2::Format::BC1_RGBA_SRGB_BLOCK => F::Bc1RgbaUnormSrgb,
+        ktx2::Format::BC3_UNORM_BLOCK => F::Bc3RgbaUnorm,
+        ktx2::Format::BC3_SRGB_BLOCK => F::Bc3RgbaUnormSrgb,
+        ktx2::Format::BC4_UNORM_BLOCK => F::Bc4RUnorm,
+        ktx2::Format::BC5_UNORM_BLOCK => F::Bc5RgUnorm,
+        ktx2::Format::BC7_UNORM_BLOCK => F::Bc7RgbaUnorm,
+        ktx2::Format::BC7_SRGB_BLOCK => F::Bc7RgbaUnormSrgb,
+        ktx2::Format::ETC2_R8G8B8_UNORM_BLOCK => F::Etc2Rgb8Unorm,
+        ktx2::Format::ETC2_R8G8B8_SRGB_BLOCK => F::Etc2Rgb8UnormSrgb,
+        ktx2::Format::ETC2_R8G8B8A8_UNORM_BLOCK => F::Etc2Rgba8Unorm,
+        ktx2::Format::ETC2_R8G8B8A8_SRGB_BLOCK => F::Etc2Rgba8UnormSrgb,
+        ktx2::Format::ASTC_4x4_UNORM_BLOCK => astc(wgpu::AstcChannel::Unorm),
+        ktx2::Format::ASTC_4x4_SRGB_BLOCK => astc(wgpu::AstcChannel::UnormSrgb),
+        _ => return None,
+    })
+}
+
+// KTX2 images in GPU formats are passed through with their mip levels. ETC1S payloads are transcoded to RGBA8,
+// UASTC ones fail and the texture falls back to its next source.
+fn decode_ktx2(data: &[u8]) -> Result<scene::Image, Box<dyn error::Error>> {
+    let reader = ktx2::Reader::new(data)?;
+    let header = reader.header();
+    if header.pixel_depth > 1 || header.layer_count > 1 || header.face_count != 1 {
+        return Err("only 2D KTX2 images are supported".into());
+    }
+
+    let (format, levels) = match (header.format, header.supercompression_scheme) {
+        (Some(format), scheme) => {
+            let format = ktx2_format(format).ok_or(format!("unsupported KTX2 format: {:?}", format))?;
+            let mut levels = Vec::new();
+            for level in reader.levels() {
+                let buffer = match scheme {
+                    None => level.data.to_vec(),
+                    Some(ktx2::SupercompressionScheme::Zstandard) => {
+                        let mut buffer = Vec::with_capacity(level.uncompressed_byte_length as usize);
+                        io::Read::read_to_end(&mut ruzstd::decoding::StreamingDecoder::new(level.data)?, &mut buffer)?;
+                        buffer
+                    }
+                    Some(scheme) => return Err(format!("unsupported KTX2 supercompression: {:?}", scheme).into()),
+                };
+                levels.push(buffer);
+            }
+            (format, levels)
+        }
+        (None, Some(ktx2::SupercompressionScheme::BasisLZ)) => {
+            let srgb = reader
+                .dfd_blocks()
+                .next()
+                .and_then(|block| ktx2::DfdBlockBasic::parse(block.data).ok())
+                .is_some_and(|block| block.header.transfer_function == Some(ktx2::TransferFunction::SRGB));
+            let levels = reader.levels().map(|level| level.data).collect::<Vec<_>>();
+            let levels = basis::transcode_etc1s(
+                reader.supercompression_global_data(),
+                &levels,
+                header.pixel_width,
+                header.pixel_height.max(1),
+            )
+            .ok_or("invalid ETC1S data")?;
+            let format = match srgb {
+                true => wgpu::TextureFormat::Rgba8UnormSrgb,
+                false => wgpu::TextureFormat::Rgba8Unorm,
+            };
+            (format, levels)
+        }
+        (None, _) => return Err("UASTC transcoding is not supported".into()),
+    };
+
+    // wgpu rejects compressed textures whose size is not a multiple of the block size.
+    let (block_w, block_h) = format.block_dimensions();
+    if header.pixel_width % block_w != 0 || header.pixel_height.max(1) % block_h != 0 {
+        return Err(format!(
+            "{}x{} is not a multiple of the {}x{} blocks of {:?}",
+            header.pixel_width, header.pixel_height, block_w, block_h, format
+        )
+        .into());
+    }
+
+    let mut levels = levels.into_iter();
+    Ok(scene::Image {
+        dims: [header.pixel_width, header.pixel_height.max(1), 4],
+        buffer: levels.next().ok_or("no KTX2 levels")?,
+        format: format,
+        mips: levels.collect(),
     })
 }
 
-pub fn load(mut f: impl io::Read) -> Result<scene::Glb, Box<dyn error::Error>> {
-    let mut header = [0; 12 + 8];
-    f.read_exact(&mut header)?;
+pub fn decode_image(data: &[u8], mime_type: &str) -> Result<scene::Image, Box<dyn error::Error>> {
+    if mime_type == "image/ktx2" || data.starts_with(b"\xabKTX 20\xbb") {
+        return decode_ktx2(data);
+    }
+    let image = match image::ImageFormat::from_mime_type(mime_type) {
+        Some(format) => image::load_from_memory_with_format(data, format)?,
+        None => image::load_from_memory(data)?,
+    };
+    Ok(scene::Image::rgba8(
+        image.width(),
+        image.height(),
+        image.into_rgba8().into_vec(),
+    ))
+}
+
+// parses the GLB in place. the BIN chunk is referenced, not copied.
+pub fn load(data: sync::Arc<dyn AsRef<[u8]> + Send + Sync>) -> Result<scene::Glb, Box<dyn error::Error>> {
+    let bytes = (*data).as_ref();
+    let header = bytes.get(..12 + 8).ok_or("")?;
     if header[0..4] != *b"glTF" {
         return Err("".into());
     }
@@ -289,24 +1384,78 @@ pub fn load(mut f: impl io::Read) -> Result<scene::Glb, Box<dyn error::Error>> {
         return Err("".into());
     }
 
-    let chunk_len = u32_from_slice(&header[12..]);
+    let chunk_len = u32_from_slice(&header[12..]) as usize;
     if header[16..20] != *b"JSON" {
         return Err("".into());
     }
-    let mut buf = vec![0; chunk_len as usize];
-    f.read_exact(&mut buf)?;
-    let json = tinyjson::JsonParser::new(str::from_utf8(&buf)?.chars()).parse()?;
+    let buf = bytes.get(20..20 + chunk_len).ok_or("")?;
+    let json = tinyjson::JsonParser::new(str::from_utf8(buf)?.chars()).parse()?;
 
-    let mut chunk_header = [0; 8];
-    f.read_exact(&mut chunk_header)?;
-    let chunk_len = u32_from_slice(&chunk_header[0..]);
+    let offset = 20 + chunk_len;
+    let chunk_header = bytes.get(offset..offset + 8).ok_or("")?;
+    let chunk_len = u32_from_slice(&chunk_header[0..]) as usize;
     if chunk_header[4..8] != *b"BIN\0" {
         return Err("".into());
     }
-    let mut blob = vec![0; chunk_len as usize];
-    f.read_exact(&mut blob)?;
+    let range = offset + 8..offset + 8 + chunk_len;
+    if range.end > bytes.len() {
+        return Err("".into());
+    }
 
-    let gltf = load_root(&json, blob).ok_or("")?;
+    let gltf = load_root(&json, scene::Blob::new(data.clone(), range)).ok_or("")?;
 
     Ok(gltf)
 }
+
+// a watched file is read instead of mapped, as an exporter truncating it would make the reads from the mapping fault.
+pub fn load_file(path: &str, watched: bool) -> Result<scene::Glb, Box<dyn error::Error>> {
+    if path.to_lowercase().ends_with(".bvh") {
+        return Ok(bvh::parse(&fs::read_to_string(path)?).ok_or("invalid BVH file")?);
+    }
+    if watched {
+        return load(sync::Arc::new(fs::read(path)?));
+    }
+    let file = fs::File::open(path)?;
+    // the mapping is released once uploading and decoding are done, so it is not held while an exporter rewrites
+    // the file.
+    let map = unsafe { memmap2::Mmap::map(&file)? };
+    load(sync::Arc::new(map))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn erases_triangles_weighted_to_the_head() {
+        // vertex 2 is weighted to joint 1 a little.
+        let joints = [
+            [0.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+        ];
+        let weights = [
+            [1.0, 0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0, 0.0],
+            [0.9, 0.1, 0.0, 0.0],
+            [1.0, 0.0, 0.0, 0.0],
+        ];
+        let indices = [0.0, 1.0, 2.0, 0.0, 1.0, 3.0];
+        let erased = [false, true];
+        assert_eq!(
+            head_less_triangles(&indices, joints.as_flattened(), weights.as_flattened(), &erased),
+            [0, 1, 3]
+        );
+    }
+
+    #[test]
+    fn skips_missing_textures() {
+        let json = r#"{"index": 1, "texCoord": 1}"#.parse::<tinyjson::JsonValue>().unwrap();
+        let textures = [vec![0]];
+        assert!(load_texture(&json, &textures).unwrap().is_none());
+        let json = r#"{"index": 0, "texCoord": 1}"#.parse::<tinyjson::JsonValue>().unwrap();
+        let texture = load_texture(&json, &textures).unwrap().unwrap();
+        assert_eq!((texture.index, texture.texcoord), (0, 1));
+    }
+}
@@ -0,0 +1,221 @@
+use crate::*;
+use nalgebra::{Matrix4, Point3, UnitQuaternion, Vector3};
+
+// maps an angle in degrees to an angle or to an expression weight.
+#[derive(Clone, Copy, Debug)]
+pub struct RangeMap {
+    pub input_max: f32,
+    pub output_scale: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Bone,
+    Expression,
+}
+
+#[derive(Clone, Debug)]
+pub struct LookAt {
+    pub offset: Vector3<f32>,
+    pub kind: Kind,
+    pub horizontal_inner: RangeMap,
+    pub horizontal_outer: RangeMap,
+    pub vertical_down: RangeMap,
+    pub vertical_up: RangeMap,
+    // from the space of the model, where it faces +z, to the space of the file. VRM 0.x models face -z.
+    pub facing: UnitQuaternion<f32>,
+}
+
+struct Eye {
+    node: usize,
+    rest_rotation: UnitQuaternion<f32>,
+    // left eyes turn outwards for positive yaws.
+    left: bool,
+}
+
+// points the eyes at a target, as rotations of the eye bones or as the lookUp, lookDown, lookLeft and lookRight
+// expressions.
+pub struct Controller {
+    head: usize,
+    head_rest: UnitQuaternion<f32>,
+    eyes: Vec<Eye>,
+}
+
+impl RangeMap {
+    pub fn map(&self, x: f32) -> f32 {
+        match self.input_max {
+            0.0 => self.output_scale,
+            input_max => x.min(input_max) / input_max * self.output_scale,
+        }
+    }
+}
+
+impl Controller {
+    pub fn new(glb: &scene::Glb) -> Option<Self> {
+        glb.look_at.as_ref()?;
+        let head = *glb.humanoid.get("head")?;
+        let worlds = glb.world_transforms();
+        let eyes = [("leftEye", true), ("rightEye", false)]
+            .into_iter()
+            .filter_map(|(name, left)| {
+                let node = *glb.humanoid.get(name)?;
+                Some(Eye {
+                    node: node,
+                    rest_rotation: glb.nodes[node].rotation,
+                    left: left,
+                })
+            })
+            .collect();
+        Some(Controller {
+            head: head,
+            head_rest: utils::rotation(&worlds[head]),
+            eyes: eyes,
+        })
+    }
+
+    // the yaw and the pitch of the target in degrees, seen from the head. a positive yaw is to the left of the
+    // model and a positive pitch is upwards.
+    pub fn angles(&self, glb: &scene::Glb, worlds: &[Matrix4<f32>], target: &Point3<f32>) -> (f32, f32) {
+        let look_at = glb.look_at.as_ref().unwrap();
+        let space = self.space(look_at, worlds);
+        let origin = worlds[self.head].transform_point(&Point3::from(look_at.offset));
+        let d = space.inverse() * (target - origin);
+        let yaw = f32::atan2(d[0], d[2]).to_degrees();
+        let pitch = f32::atan2(d[1], f32::hypot(d[0], d[2])).to_degrees();
+        (yaw, pitch)
+    }
+
+    // the rotation of the head relative to its rest pose, in the space of the model.
+    fn space(&self, look_at: &LookAt, worlds: &[Matrix4<f32>]) -> UnitQuaternion<f32> {
+        utils::rotation(&worlds[self.head]) * self.head_rest.inverse() * look_at.facing
+    }
+
+    // looks at the target in world space, or straight ahead if there is none. returns the materials to be uploaded
+    // again.
+    pub fn update(&self, glb: &mut scene::Glb, placement: &Matrix4<f32>, target: Option<&Point3<f32>>) -> Vec<usize> {
+        if glb.look_at.is_none() {
+            return Vec::new();
+        }
+        let worlds = glb.world_transforms();
+        let inverse = placement.try_inverse().unwrap_or_else(Matrix4::identity);
+        let target = target.map(|t| inverse.transform_point(t));
+        let (yaw, pitch) = target.map_or((0.0, 0.0), |t| self.angles(glb, &worlds, &t));
+        self.turn(glb, yaw, pitch)
+    }
+
+    // turns the eyes by the angles in degrees, as given by angles().
+    pub fn turn(&self, glb: &mut scene::Glb, yaw: f32, pitch: f32) -> Vec<usize> {
+        let Some(look_at) = glb.look_at.clone() else {
+            return Vec::new();
+        };
+        let worlds = glb.world_transforms();
+        let vertical = match pitch >= 0.0 {
+            true => look_at.vertical_up.map(pitch),
+            false => -look_at.vertical_down.map(-pitch),
+        };
+        match look_at.kind {
+            Kind::Bone => {
+                let space = self.space(&look_at, &worlds);
+                for eye in self.eyes.iter() {
+                    let horizontal = match (yaw >= 0.0) == eye.left {
+                        true => look_at.horizontal_outer.map(yaw.abs()),
+                        false => look_at.horizontal_inner.map(yaw.abs()),
+                    };
+                    let q = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), horizontal.copysign(yaw).to_radians())
+                        * UnitQuaternion::from_axis_angle(&Vector3::x_axis(), -vertical.to_radians());
+                    let parent = glb.nodes.iter().position(|n| n.children.contains(&eye.node));
+                    let parent = parent.map_or_else(UnitQuaternion::identity, |p| utils::rotation(&worlds[p]));
+                    let world = space * q * space.inverse() * parent * eye.rest_rotation;
+                    glb.nodes[eye.node].rotation = parent.inverse() * world;
+                }
+                Vec::new()
+            }
+            Kind::Expression => {
+                let horizontal = look_at.horizontal_outer.map(yaw.abs());
+                let expressions = &mut glb.expressions;
+                expressions.set_weight("lookLeft", if yaw > 0.0 { horizontal } else { 0.0 });
+                expressions.set_weight("lookRight", if yaw < 0.0 { horizontal } else { 0.0 });
+                expressions.set_weight("lookUp", vertical.max(0.0));
+                expressions.set_weight("lookDown", (-vertical).max(0.0));
+                glb.apply_expressions()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a head at (0, 1, 0) with the eyes in front of it.
+    fn head(kind: Kind) -> scene::Glb {
+        let range = RangeMap {
+            input_max: 90.0,
+            output_scale: 45.0,
+        };
+        let eye = |x| scene::Node {
+            translation: Vector3::new(x, 0.0, 0.1),
+            ..Default::default()
+        };
+        let nodes = vec![
+            scene::Node {
+                translation: Vector3::new(0.0, 1.0, 0.0),
+                children: vec![1, 2],
+                ..Default::default()
+            },
+            eye(0.03),
+            eye(-0.03),
+        ];
+        let humanoid = [("head", 0), ("leftEye", 1), ("rightEye", 2)];
+        scene::Glb {
+            nodes: nodes,
+            roots: vec![0],
+            humanoid: humanoid.into_iter().map(|(k, v)| (k.to_string(), v)).collect(),
+            look_at: Some(LookAt {
+                offset: Vector3::new(0.0, 0.0, 0.1),
+                kind: kind,
+                horizontal_inner: range,
+                horizontal_outer: range,
+                vertical_down: range,
+                vertical_up: range,
+                facing: UnitQuaternion::identity(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn maps_ranges() {
+        let range = RangeMap {
+            input_max: 20.0,
+            output_scale: 10.0,
+        };
+        assert_eq!(range.map(10.0), 5.0);
+        assert_eq!(range.map(40.0), 10.0);
+    }
+
+    #[test]
+    fn measures_angles_from_the_head() {
+        let glb = head(Kind::Bone);
+        let controller = Controller::new(&glb).unwrap();
+        let worlds = glb.world_transforms();
+        let (yaw, pitch) = controller.angles(&glb, &worlds, &Point3::new(1.0, 1.0, 1.1));
+        assert!((yaw - 45.0).abs() < 1e-3 && pitch.abs() < 1e-3, "{} {}", yaw, pitch);
+        let (yaw, pitch) = controller.angles(&glb, &worlds, &Point3::new(0.0, 0.0, 1.1));
+        assert!(yaw.abs() < 1e-3 && (pitch + 45.0).abs() < 1e-3, "{} {}", yaw, pitch);
+    }
+
+    #[test]
+    fn turns_the_eyes() {
+        let mut glb = head(Kind::Bone);
+        let controller = Controller::new(&glb).unwrap();
+        controller.update(&mut glb, &Matrix4::identity(), Some(&Point3::new(10.0, 1.0, 10.1)));
+        for eye in [1, 2] {
+            let forward = glb.nodes[eye].rotation * Vector3::z();
+            let yaw = f32::atan2(forward[0], forward[2]).to_degrees();
+            assert!((yaw - 22.5).abs() < 1e-3, "{}", yaw);
+        }
+        controller.update(&mut glb, &Matrix4::identity(), None);
+        assert!(glb.nodes[1].rotation.angle() < 1e-6);
+    }
+}
@@ -1,95 +1,565 @@
-use nalgebra::{UnitQuaternion, Vector3};
+use nalgebra::{Point3, UnitQuaternion, Vector3};
 use std::*;
-use winit::{event, event_loop, window};
+use winit::{event, event_loop, keyboard, window};
+mod animation;
+mod basis;
 mod blocking;
+mod bvh;
+mod expression;
+mod frustum;
 mod gpu_resource;
 mod loader;
+mod look_at;
 //mod node;
+mod options;
+mod outline;
+mod overlay;
+mod pick;
+mod pose;
+mod post;
 mod renderer;
+mod retarget;
 mod scene;
+mod spring;
 mod utils;
+mod watch;
+mod world;
+
+// the expression presets toggled by the number keys 1 to 9 on all the models. 0 resets the expressions.
+const EXPRESSION_KEYS: [&str; 9] = [
+    "happy",
+    "angry",
+    "sad",
+    "relaxed",
+    "surprised",
+    "aa",
+    "ih",
+    "ou",
+    "blink",
+];
+
+// the pose edited with the keyboard, shared by all the humanoid models.
+struct Posing {
+    pose: pose::HumanoidPose,
+    bone: &'static str,
+}
 
 struct WgpuWindow {
     window: sync::Arc<window::Window>,
     surface: wgpu::Surface<'static>,
+    adapter: wgpu::Adapter,
     device: wgpu::Device,
     queue: wgpu::Queue,
+    format: wgpu::TextureFormat,
+    view_format: wgpu::TextureFormat,
+    present_mode: wgpu::PresentMode,
+    frame_latency: u32,
+    immediates: bool,
+}
+
+enum UserEvent {
+    Loaded {
+        path: String,
+        id: usize,
+        replace: Option<usize>,
+        glb: Result<scene::Glb, String>,
+    },
+    // the image of the textures with the same sources.
+    ImageDecoded {
+        id: usize,
+        textures: Vec<usize>,
+        image: Result<scene::Image, String>,
+    },
+    Changed(String),
 }
 
 struct App {
     window: Option<WgpuWindow>,
     renderer: Option<renderer::Renderer>,
-    glb: scene::Glb,
+    world: world::World,
+    options: options::Options,
+    proxy: event_loop::EventLoopProxy<UserEvent>,
+    error: Option<String>,
+    last_frame: Option<time::Instant>,
+    look_at: bool,
+    posing: Option<Posing>,
+    // in physical pixels.
+    cursor: Option<(f32, f32)>,
+    // the id of the model and where it was clicked.
+    selection: Option<(usize, pick::Hit)>,
+    // takes the dropped files for the watcher with --watch.
+    watcher: Option<sync::mpsc::Sender<String>>,
+    // the watched files which could not be loaded, such as a half-written export. they are loaded again when they
+    // change.
+    failed: Vec<String>,
+}
+
+fn create_instance(display: event_loop::OwnedDisplayHandle) -> wgpu::Instance {
+    wgpu::Instance::new(wgpu::InstanceDescriptor {
+        flags: wgpu::InstanceFlags::ALLOW_UNDERLYING_NONCOMPLIANT_ADAPTER,
+        ..wgpu::InstanceDescriptor::new_with_display_handle_from_env(Box::new(display))
+    })
+}
+
+fn list_adapters(event_loop: &event_loop::EventLoop<UserEvent>) {
+    let instance = create_instance(event_loop.owned_display_handle());
+    for (i, adapter) in blocking::block_on(instance.enumerate_adapters(wgpu::Backends::all()))
+        .iter()
+        .enumerate()
+    {
+        let info = adapter.get_info();
+        println!(
+            "{}: {} ({:?}, {:?}, {} {})",
+            i, info.name, info.backend, info.device_type, info.driver, info.driver_info
+        );
+    }
+}
+
+// loads the files one by one on a worker thread. "replace" is the id of the model to be replaced. the scene is
+// sent as soon as the geometry is ready and the images follow as they are decoded in parallel.
+fn spawn_loader(
+    proxy: event_loop::EventLoopProxy<UserEvent>,
+    jobs: Vec<(String, usize, Option<usize>)>,
+    watched: bool,
+) {
+    thread::spawn(move || {
+        for (path, id, replace) in jobs {
+            let time = time::Instant::now();
+            let glb = loader::load_file(&path, watched).map_err(|e| format!("{}: {}", path, e));
+            println!("loader::load(): {:?}", time.elapsed());
+
+            // the blob is shared with the decoders while the scene is handed over to the event loop. only the textures
+            // sampled by materials are decoded, once for all the textures with the same images.
+            let (blob, sources, chains) = match &glb {
+                Ok(glb) => {
+                    let mut chains = Vec::<(Vec<usize>, Vec<usize>)>::new();
+                    for texture in glb.materials.iter().flat_map(|m| m.sampled_textures()) {
+                        let images = &glb.textures[texture.index];
+                        match chains.iter_mut().find(|(c, _)| c == images) {
+                            Some((_, textures)) if textures.contains(&texture.index) => (),
+                            Some((_, textures)) => textures.push(texture.index),
+                            None => chains.push((images.clone(), vec![texture.index])),
+                        }
+                    }
+                    (glb.blob.clone(), glb.images.clone(), chains)
+                }
+                Err(_) => (None, Vec::new(), Vec::new()),
+            };
+            if proxy
+                .send_event(UserEvent::Loaded {
+                    path: path.clone(),
+                    id: id,
+                    replace: replace,
+                    glb: glb,
+                })
+                .is_err()
+            {
+                return;
+            }
+
+            let time = time::Instant::now();
+            let shared = sync::Mutex::new(proxy.clone());
+            utils::par_for_each(&chains, |_, (images, textures)| {
+                let Some(blob) = &blob else {
+                    return;
+                };
+                let mut errors = Vec::new();
+                let image = images.iter().copied().find_map(|j| {
+                    let image = match sources.get(j) {
+                        Some(Some(source)) => loader::decode_image(&blob[source.range.clone()], &source.mime_type)
+                            .map_err(|e| format!("{}: image {}: {}", path, j, e)),
+                        _ => Err(format!(
+                            "{}: image {}: only images in the GLB buffer are supported",
+                            path, j
+                        )),
+                    };
+                    image.map_err(|e| errors.push(e)).ok()
+                });
+                let image = match image {
+                    Some(image) => {
+                        for err in errors {
+                            eprintln!("{}, using the fallback.", err);
+                        }
+                        Ok(image)
+                    }
+                    None => Err(errors.join("\n")),
+                };
+                let _ = shared.lock().unwrap().send_event(UserEvent::ImageDecoded {
+                    id: id,
+                    textures: textures.clone(),
+                    image: image,
+                });
+            });
+            println!("loader::decode_image(): {:?}", time.elapsed());
+        }
+    });
+}
+
+// the returned sender adds files to the watched ones.
+fn spawn_watcher(proxy: event_loop::EventLoopProxy<UserEvent>, paths: Vec<String>) -> sync::mpsc::Sender<String> {
+    let (sender, receiver) = sync::mpsc::channel();
+    thread::spawn(move || {
+        let mut watcher = watch::Watcher::new(paths);
+        loop {
+            thread::sleep(watch::Watcher::INTERVAL);
+            for path in receiver.try_iter() {
+                watcher.add(path);
+            }
+            for path in watcher.poll() {
+                if proxy.send_event(UserEvent::Changed(path)).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    sender
+}
+
+impl Posing {
+    const STEP: f32 = 5.0;
+    const PATH: &str = "pose.json";
+
+    // handles the keys of the pose mode. returns false for the other keys.
+    fn key(&mut self, key: keyboard::Key<&str>, bones: &[&'static str]) -> bool {
+        use keyboard::{Key, NamedKey};
+        let turn = match key {
+            Key::Named(NamedKey::ArrowUp) => Some((Vector3::x(), -Self::STEP)),
+            Key::Named(NamedKey::ArrowDown) => Some((Vector3::x(), Self::STEP)),
+            Key::Named(NamedKey::ArrowLeft) => Some((Vector3::y(), -Self::STEP)),
+            Key::Named(NamedKey::ArrowRight) => Some((Vector3::y(), Self::STEP)),
+            Key::Named(NamedKey::PageUp) => Some((Vector3::z(), Self::STEP)),
+            Key::Named(NamedKey::PageDown) => Some((Vector3::z(), -Self::STEP)),
+            _ => None,
+        };
+        if let Some((axis, angle)) = turn {
+            self.pose.rotate(self.bone, axis, angle);
+            let (x, y, z) = self.pose.rotation(self.bone).euler_angles();
+            println!(
+                "{}: {:.0} {:.0} {:.0}",
+                self.bone,
+                x.to_degrees(),
+                y.to_degrees(),
+                z.to_degrees()
+            );
+            return true;
+        }
+        match key {
+            Key::Character(c @ ("[" | "]")) if !bones.is_empty() => {
+                let i = bones.iter().position(|b| *b == self.bone).unwrap_or(0);
+                let i = if c == "]" { i + 1 } else { i + bones.len() - 1 };
+                self.bone = bones[i % bones.len()];
+                println!("bone: {}", self.bone);
+            }
+            Key::Named(f @ (NamedKey::F1 | NamedKey::F2 | NamedKey::F3)) => {
+                let name = pose::PRESETS[f as usize - NamedKey::F1 as usize];
+                self.pose = pose::HumanoidPose::preset(name).unwrap();
+                println!("pose: {}", name);
+            }
+            Key::Character("s") => match fs::write(Self::PATH, self.pose.to_json()) {
+                Ok(()) => println!("saved: {}", Self::PATH),
+                Err(err) => eprintln!("{}: {}", Self::PATH, err),
+            },
+            Key::Character("o") => match load_pose(Self::PATH) {
+                Ok(pose) => self.pose = pose,
+                Err(err) => eprintln!("{}", err),
+            },
+            _ => return false,
+        }
+        true
+    }
+}
+
+// the camera at its fixed place, or at the eyes of a model in the first person view. it looks at -z.
+fn camera(eye: Option<(Point3<f32>, UnitQuaternion<f32>)>) -> scene::Node {
+    let mut camera = scene::Node {
+        //translation: Vector3::new(0.0, 0.75, -3.0),
+        translation: Vector3::new(0.0, 1.0, 2.0),
+        //rotation: UnitQuaternion::from_euler_angles(f32::consts::PI / 20.0, f32::consts::PI, 0.0),
+        rotation: UnitQuaternion::from_euler_angles(f32::consts::PI / -20.0, 0.0, 0.0),
+        scale: Vector3::new(1.0, 1.0, -0.75),
+        ..Default::default()
+    };
+    if let Some((eye, rotation)) = eye {
+        camera.translation = eye.coords;
+        camera.rotation = rotation * UnitQuaternion::from_axis_angle(&Vector3::y_axis(), f32::consts::PI);
+    }
+    camera
+}
+
+fn load_pose(path: &str) -> Result<pose::HumanoidPose, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    pose::HumanoidPose::from_json(&text).ok_or(format!("{}: invalid pose", path))
 }
 
 impl WgpuWindow {
-    pub fn new(event_loop: &event_loop::ActiveEventLoop) -> Result<Self, Box<dyn error::Error>> {
+    pub fn new(
+        event_loop: &event_loop::ActiveEventLoop,
+        options: &options::Options,
+    ) -> Result<Self, Box<dyn error::Error>> {
         let window =
             sync::Arc::new(event_loop.create_window(window::Window::default_attributes().with_visible(false))?);
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            flags: wgpu::InstanceFlags::ALLOW_UNDERLYING_NONCOMPLIANT_ADAPTER,
-            ..wgpu::InstanceDescriptor::new_with_display_handle_from_env(Box::new(event_loop.owned_display_handle()))
-        });
+        let instance = create_instance(event_loop.owned_display_handle());
         let surface = instance.create_surface(window.clone())?;
-        let adapter = blocking::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-            compatible_surface: Some(&surface),
-            ..Default::default()
-        }))?;
+        let adapter = match &options.adapter {
+            Some(query) => {
+                blocking::block_on(instance.enumerate_adapters(wgpu::Backends::all()))
+                    .into_iter()
+                    .enumerate()
+                    .find(|(i, adapter)| {
+                        let name = adapter.get_info().name.to_lowercase();
+                        (query.parse() == Ok(*i) || name.contains(&query.to_lowercase()))
+                            && adapter.is_surface_supported(&surface)
+                    })
+                    .ok_or(format!("no adapter matches \"{}\", see --list-adapters", query))?
+                    .1
+            }
+            None => blocking::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                compatible_surface: Some(&surface),
+                ..Default::default()
+            }))?,
+        };
+
+        let info = adapter.get_info();
+        let features = adapter.features();
+        println!("adapter: {} ({:?}, {:?})", info.name, info.backend, info.device_type);
+        // all of them are optional, the missing ones are reported and worked around.
+        let requested_features = wgpu::Features::IMMEDIATES
+            | wgpu::Features::STORAGE_RESOURCE_BINDING_ARRAY
+            | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
+            | wgpu::Features::TEXTURE_COMPRESSION_BC
+            | wgpu::Features::TEXTURE_COMPRESSION_ETC2
+            | wgpu::Features::TEXTURE_COMPRESSION_ASTC;
+        let missing = (requested_features - features)
+            .iter_names()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            eprintln!("{}: {} not supported.", info.name, missing.join(", "));
+        }
+        let immediates = features.contains(wgpu::Features::IMMEDIATES)
+            && adapter.limits().max_immediate_size >= renderer::Renderer::IMMEDIATE_SIZE;
+        if !immediates {
+            eprintln!(
+                "{}: {} bytes of immediates are not supported, falling back to uniform buffers.",
+                info.name,
+                renderer::Renderer::IMMEDIATE_SIZE
+            );
+        }
+
+        let required_features = features & requested_features;
         let (device, queue) = blocking::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
-            required_features: wgpu::Features::IMMEDIATES
-                | wgpu::Features::STORAGE_RESOURCE_BINDING_ARRAY
-                | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
+            required_features: required_features,
             required_limits: wgpu::Limits {
-                max_immediate_size: 128,
+                max_immediate_size: if immediates {
+                    renderer::Renderer::IMMEDIATE_SIZE
+                } else {
+                    0
+                },
                 ..Default::default()
             },
             ..Default::default()
-        }))?;
+        }))
+        .map_err(|e| format!("{} ({:?}): {}", info.name, info.backend, e))?;
+
+        let caps = surface.get_capabilities(&adapter);
+        let format = match caps.formats.iter().find(|f| f.is_srgb()) {
+            Some(format) => *format,
+            None => *caps
+                .formats
+                .first()
+                .ok_or("the surface is incompatible with the adapter")?,
+        };
+        let present_mode = match options.present_mode {
+            wgpu::PresentMode::Mailbox | wgpu::PresentMode::Immediate
+                if !caps.present_modes.contains(&options.present_mode) =>
+            {
+                eprintln!(
+                    "{:?} is not supported by the surface, falling back to vsync.",
+                    options.present_mode
+                );
+                wgpu::PresentMode::AutoVsync
+            }
+            mode => mode,
+        };
         window.set_visible(true);
 
         Ok(Self {
             window: window,
             surface: surface,
+            adapter: adapter,
             device: device,
             queue: queue,
+            format: format,
+            view_format: format.add_srgb_suffix(),
+            present_mode: present_mode,
+            frame_latency: options.frame_latency,
+            immediates: immediates,
         })
     }
 
-    pub fn resize(&mut self, w: u32, h: u32, format: wgpu::TextureFormat) {
+    pub fn sample_counts(&self) -> Vec<u32> {
+        let specific = self
+            .device
+            .features()
+            .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+        let color = self.adapter.get_texture_format_features(renderer::Renderer::HDR_FORMAT);
+        let depth = self
+            .adapter
+            .get_texture_format_features(wgpu::TextureFormat::Depth32Float);
+        [1, 2, 4, 8]
+            .into_iter()
+            .filter(|n| {
+                (specific || *n == 1 || *n == 4)
+                    && color.flags.sample_count_supported(*n)
+                    && depth.flags.sample_count_supported(*n)
+            })
+            .collect()
+    }
+
+    pub fn resize(&mut self, w: u32, h: u32) {
         self.surface.configure(
             &self.device,
             &wgpu::SurfaceConfiguration {
                 usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-                format: format,
+                format: self.format,
                 width: w,
                 height: h,
-                present_mode: wgpu::PresentMode::AutoVsync,
-                desired_maximum_frame_latency: 2,
+                present_mode: self.present_mode,
+                desired_maximum_frame_latency: self.frame_latency,
                 alpha_mode: wgpu::CompositeAlphaMode::Auto,
-                view_formats: Vec::new(),
+                view_formats: if self.view_format != self.format {
+                    vec![self.view_format]
+                } else {
+                    Vec::new()
+                },
             },
         );
     }
 }
 
 impl App {
-    fn new(glb: scene::Glb) -> Self {
+    fn new(options: options::Options, proxy: event_loop::EventLoopProxy<UserEvent>) -> Self {
+        let watcher = match options.watch {
+            true => Some(spawn_watcher(proxy.clone(), options.paths.clone())),
+            false => None,
+        };
         Self {
             window: None,
             renderer: None,
-            glb: glb,
+            world: world::World::new(),
+            options: options,
+            proxy: proxy,
+            error: None,
+            last_frame: None,
+            look_at: true,
+            posing: None,
+            cursor: None,
+            selection: None,
+            watcher: watcher,
+            failed: Vec::new(),
+        }
+    }
+
+    // shows the loaded files and the selected node, or the last error, over the image and in the window title.
+    fn update_status(&mut self) {
+        let (Some(window), Some(renderer)) = (self.window.as_ref(), self.renderer.as_mut()) else {
+            return;
+        };
+        let status = match &self.error {
+            Some(err) => format!("error: {}", err),
+            None => {
+                let paths = self.world.models.iter().map(|m| m.path.as_str()).collect::<Vec<_>>();
+                let selected = self.selection.and_then(|(id, hit)| {
+                    let node = &self.world.get(id)?.glb.nodes[hit.node];
+                    Some(match node.name.as_str() {
+                        "" => format!(" - node {}", hit.node),
+                        name => format!(" - {}", name),
+                    })
+                });
+                format!("{}{}", paths.join(", "), selected.unwrap_or_default())
+            }
+        };
+        window
+            .window
+            .set_title(&format!("yavv - {}", status.replace('\n', " ")));
+        renderer.overlay.set_text(&window.device, &window.queue, &status);
+        window.window.request_redraw();
+    }
+
+    fn load(&mut self, jobs: Vec<(String, Option<usize>)>) {
+        let jobs = jobs
+            .into_iter()
+            .map(|(path, replace)| (path, self.world.reserve_id(), replace))
+            .collect();
+        spawn_loader(self.proxy.clone(), jobs, self.options.watch);
+    }
+
+    fn update(&mut self) {
+        let (Some(window), Some(renderer)) = (self.window.as_ref(), self.renderer.as_mut()) else {
+            return;
+        };
+        renderer.update(&window.device, &self.world);
+        // the GPU has its copy now, and the decoders hold their own.
+        for model in self.world.models.iter_mut().filter(|m| renderer.has_model(m.id)) {
+            model.glb.blob = None;
         }
+        window.window.request_redraw();
     }
 }
 
-impl winit::application::ApplicationHandler for App {
+impl winit::application::ApplicationHandler<UserEvent> for App {
     fn resumed(&mut self, event_loop: &event_loop::ActiveEventLoop) {
-        let window = WgpuWindow::new(event_loop).unwrap();
-        let mut renderer = renderer::Renderer::new(&window.device, 4).unwrap();
-        renderer.update(&window.device, &window.queue, &self.glb);
+        let window = match WgpuWindow::new(event_loop, &self.options) {
+            Ok(window) => window,
+            Err(err) => {
+                eprintln!("{}", err);
+                event_loop.exit();
+                return;
+            }
+        };
+        let sample_counts = window.sample_counts();
+        let sample_count = match sample_counts.iter().rev().find(|n| **n <= self.options.msaa) {
+            Some(n) if *n == self.options.msaa => *n,
+            n => {
+                let n = n.copied().unwrap_or(1);
+                eprintln!("{}x MSAA is not supported, using {}x.", self.options.msaa, n);
+                n
+            }
+        };
+        let mut renderer = renderer::Renderer::new(
+            &window.device,
+            &window.queue,
+            window.view_format,
+            sample_count,
+            window.immediates,
+        )
+        .unwrap();
+        renderer.update(&window.device, &self.world);
         renderer.set_projection_scale(1.0 / 3.0);
+        let background = mem::replace(&mut self.options.background, overlay::Background::Color([0.0; 4]));
+        renderer
+            .overlay
+            .set_background(&window.device, &window.queue, background);
+        renderer.overlay.grid = self.options.grid;
+        renderer.overlay.axes = self.options.axes;
+        renderer.post.tone_mapping = self.options.tone_mapping;
+        renderer.post.exposure = self.options.exposure;
+        renderer.post.bloom = self.options.bloom;
         self.window = Some(window);
         self.renderer = Some(renderer);
+        self.update_status();
+
+        // images are uploaded as they arrive, so loading starts once the renderer exists. the models whose blob was
+        // released are loaded again for a renderer created on resume.
+        let reloads = self
+            .world
+            .models
+            .iter()
+            .filter(|m| m.glb.blob.is_none())
+            .map(|m| (m.path.clone(), Some(m.id)))
+            .collect::<Vec<_>>();
+        let paths = mem::take(&mut self.options.paths);
+        self.load(paths.into_iter().map(|p| (p, None)).chain(reloads).collect());
     }
 
     fn window_event(
@@ -98,8 +568,9 @@ impl winit::application::ApplicationHandler for App {
         window_id: window::WindowId,
         event: event::WindowEvent,
     ) {
-        let window = self.window.as_mut().unwrap();
-        let renderer = self.renderer.as_mut().unwrap();
+        let (Some(window), Some(renderer)) = (self.window.as_mut(), self.renderer.as_mut()) else {
+            return;
+        };
         if window_id != window.window.id() {
             return;
         }
@@ -110,51 +581,309 @@ impl winit::application::ApplicationHandler for App {
             event::WindowEvent::Resized(size) => {
                 let w = size.width.max(1);
                 let h = size.height.max(1);
-                window.resize(w, h, renderer::Renderer::FORMAT);
+                window.resize(w, h);
                 renderer.resize(&window.device, w, h);
             }
+            event::WindowEvent::DroppedFile(path) => {
+                let path = path.to_string_lossy().into_owned();
+                if !path.to_lowercase().ends_with(".json") {
+                    if let Some(watcher) = &self.watcher {
+                        let _ = watcher.send(path.clone());
+                    }
+                    self.load(vec![(path, None)]);
+                    return;
+                }
+                match load_pose(&path) {
+                    Ok(pose) => {
+                        let bone = self.posing.as_ref().map_or("hips", |p| p.bone);
+                        self.posing = Some(Posing { pose: pose, bone: bone });
+                        println!("pose: {}", path);
+                    }
+                    Err(err) => eprintln!("{}", err),
+                }
+                window.window.request_redraw();
+            }
+            event::WindowEvent::KeyboardInput { event, .. }
+                if event.state.is_pressed()
+                    && self
+                        .posing
+                        .as_mut()
+                        .is_some_and(|posing| posing.key(event.logical_key.as_ref(), &self.world.humanoid_bones())) =>
+            {
+                window.window.request_redraw();
+            }
+            event::WindowEvent::KeyboardInput { event, .. } if event.state.is_pressed() => {
+                match event.logical_key.as_ref() {
+                    keyboard::Key::Character("g") => renderer.overlay.grid = !renderer.overlay.grid,
+                    keyboard::Key::Character("a") => renderer.overlay.axes = !renderer.overlay.axes,
+                    keyboard::Key::Character("t") => renderer.post.tone_mapping = renderer.post.tone_mapping.next(),
+                    keyboard::Key::Character("b") => renderer.post.bloom = !renderer.post.bloom,
+                    keyboard::Key::Character("m") => {
+                        let sample_counts = window.sample_counts();
+                        let i = sample_counts
+                            .iter()
+                            .position(|n| *n == renderer.sample_count())
+                            .unwrap_or(0);
+                        let sample_count = sample_counts[(i + 1) % sample_counts.len()];
+                        renderer.set_sample_count(&window.device, sample_count);
+                        println!("MSAA: {}x", sample_count);
+                    }
+                    keyboard::Key::Named(keyboard::NamedKey::Delete | keyboard::NamedKey::Backspace) => {
+                        let Some(id) = self.world.models.last().map(|m| m.id) else {
+                            return;
+                        };
+                        if let Some(model) = self.world.remove(id) {
+                            println!("removed: {}", model.path);
+                        }
+                        renderer.update(&window.device, &self.world);
+                    }
+                    keyboard::Key::Character("r") => {
+                        let jobs = self.world.models.iter().map(|m| (m.path.clone(), Some(m.id))).collect();
+                        self.load(jobs);
+                        return;
+                    }
+                    keyboard::Key::Character(c) if c.len() == 1 && c.as_bytes()[0].is_ascii_digit() => {
+                        let name = match (c.as_bytes()[0] - b'0') as usize {
+                            0 => None,
+                            i => Some(EXPRESSION_KEYS[i - 1]),
+                        };
+                        for model in self.world.models.iter_mut() {
+                            let expressions = &mut model.glb.expressions;
+                            match name {
+                                Some(name) => {
+                                    let weight = expressions.weight(name).unwrap_or(0.0);
+                                    if expressions.set_weight(name, 1.0 - weight.round()) {
+                                        println!("{}: {}", name, 1.0 - weight.round());
+                                    }
+                                }
+                                None => expressions.clear(),
+                            }
+                            for material in model.glb.apply_expressions() {
+                                renderer.update_material(&window.queue, model, material);
+                            }
+                        }
+                    }
+                    keyboard::Key::Character("f") => {
+                        renderer.first_person = !renderer.first_person;
+                        println!("first person: {}", renderer.first_person);
+                    }
+                    keyboard::Key::Character("e") => {
+                        self.posing = match self.posing.take() {
+                            Some(_) => None,
+                            None => {
+                                // the edit starts from the pose of the first humanoid model.
+                                let model = self.world.models.iter().find(|m| !m.glb.humanoid.is_empty());
+                                let pose = model.map(|m| pose::HumanoidPose::capture(&m.glb, &m.rest));
+                                for model in self.world.models.iter_mut() {
+                                    if let Some(player) = &mut model.player {
+                                        player.playing = false;
+                                    }
+                                }
+                                Some(Posing {
+                                    pose: pose.unwrap_or_default(),
+                                    bone: "hips",
+                                })
+                            }
+                        };
+                        println!("pose mode: {}", self.posing.is_some());
+                    }
+                    keyboard::Key::Character("l") => {
+                        self.look_at = !self.look_at;
+                        println!("look at the camera: {}", self.look_at);
+                    }
+                    keyboard::Key::Named(keyboard::NamedKey::Space) => {
+                        for model in self.world.models.iter_mut() {
+                            if let Some(player) = &mut model.player {
+                                player.playing = !player.playing;
+                            }
+                        }
+                    }
+                    keyboard::Key::Character("n") => {
+                        for model in self.world.models.iter_mut() {
+                            if let Some(player) = &mut model.player {
+                                *player = animation::Player::new((player.clip + 1) % model.glb.animations.len());
+                                let clip = &model.glb.animations[player.clip];
+                                println!("{}: clip {} {}", model.path, player.clip, clip.name);
+                            }
+                        }
+                    }
+                    keyboard::Key::Character("+") => renderer.post.exposure += 0.5,
+                    keyboard::Key::Character("-") => renderer.post.exposure -= 0.5,
+                    _ => return,
+                }
+                window.window.request_redraw();
+            }
+            event::WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = Some((position.x as f32, position.y as f32));
+            }
+            event::WindowEvent::MouseInput {
+                state: event::ElementState::Pressed,
+                button: event::MouseButton::Left,
+                ..
+            } => {
+                let Some((x, y)) = self.cursor else {
+                    return;
+                };
+                // the nodes are where they were drawn last.
+                let eye = renderer.first_person.then(|| self.world.first_person_eye()).flatten();
+                let ray = renderer.ray(&camera(eye), x, y);
+                self.selection = self.world.pick(&ray, renderer.first_person);
+                renderer.selected = self.selection.map(|(id, hit)| (id, hit.node));
+                match self.selection.and_then(|(id, hit)| Some((self.world.get(id)?, hit))) {
+                    Some((model, hit)) => {
+                        let primitive = &model.glb.meshes[hit.mesh].primitives[hit.primitive];
+                        let [u, v, w] = hit.barycentric.into();
+                        let p = ray.at(hit.distance);
+                        println!(
+                            "{}: node {} {:?}, mesh {}, primitive {}, material {:?}, triangle {}, vertex {}, \
+                             barycentric ({:.3}, {:.3}, {:.3}), at ({:.3}, {:.3}, {:.3})",
+                            model.path,
+                            hit.node,
+                            model.glb.nodes[hit.node].name,
+                            hit.mesh,
+                            hit.primitive,
+                            primitive.material,
+                            hit.triangle,
+                            hit.vertex(),
+                            u,
+                            v,
+                            w,
+                            p.x,
+                            p.y,
+                            p.z,
+                        );
+                    }
+                    None => println!("selected: none"),
+                }
+                window.window.request_redraw();
+                self.update_status();
+            }
             event::WindowEvent::RedrawRequested => {
                 let wgpu::CurrentSurfaceTexture::Success(frame) = window.surface.get_current_texture() else {
                     return;
                 };
-                let frame_view = frame.texture.create_view(&Default::default());
+                let frame_view = frame.texture.create_view(&wgpu::TextureViewDescriptor {
+                    format: Some(window.view_format),
+                    ..Default::default()
+                });
+
+                let first_person = renderer.first_person.then(|| self.world.first_person_eye()).flatten();
+                let camera = camera(first_person);
+
+                // the clips, the springs and the eyes are updated before the nodes are collected for rendering.
+                let now = time::Instant::now();
+                let dt = self.last_frame.map_or(0.0, |t| (now - t).as_secs_f32());
+                self.last_frame = Some(now);
+                let (animated, materials) = self.world.animate(dt, self.posing.as_ref().map(|p| &p.pose));
+                for (i, material) in materials {
+                    renderer.update_material(&window.queue, &self.world.models[i], material);
+                }
+                let target = Point3::from(camera.translation);
+                let look_at = self.look_at && first_person.is_none();
+                for (i, material) in self.world.look_at(look_at.then_some(&target)) {
+                    renderer.update_material(&window.queue, &self.world.models[i], material);
+                }
 
                 let time = time::Instant::now();
                 let mut encoder = window.device.create_command_encoder(&Default::default());
-                renderer.render(
-                    &mut encoder,
-                    &self.glb,
-                    &frame_view,
-                    &scene::Node {
-                        //translation: Vector3::new(0.0, 0.75, -3.0),
-                        translation: Vector3::new(0.0, 1.0, 2.0),
-                        //rotation: UnitQuaternion::from_euler_angles(f32::consts::PI / 20.0, f32::consts::PI, 0.0),
-                        rotation: UnitQuaternion::from_euler_angles(f32::consts::PI / -20.0, 0.0, 0.0),
-                        scale: Vector3::new(1.0, 1.0, -0.75),
-                        ..Default::default()
-                    },
-                );
+                renderer.render(&mut encoder, &window.queue, &self.world, &frame_view, &camera);
                 let command_buffer = encoder.finish();
                 println!("{:?}", time.elapsed());
 
                 window.queue.submit(Some(command_buffer));
                 frame.present();
+                if animated {
+                    window.window.request_redraw();
+                }
             }
             _ => (),
         }
     }
+
+    fn user_event(&mut self, _: &event_loop::ActiveEventLoop, event: UserEvent) {
+        match event {
+            UserEvent::Loaded { path, id, replace, glb } => {
+                let glb = match glb {
+                    Ok(glb) => glb,
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        if self.watcher.is_some() && replace.is_none() && !self.failed.contains(&path) {
+                            self.failed.push(path);
+                        }
+                        self.error = Some(err);
+                        self.update_status();
+                        return;
+                    }
+                };
+                self.error = None;
+                // a file without meshes is a motion for the models which are already there.
+                if glb.meshes.is_empty() && !glb.animations.is_empty() && replace.is_none() {
+                    match self.world.add_motion(glb) {
+                        0 => println!("{}: no models to retarget onto, kept for the models loaded later", path),
+                        count => println!("{}: retargeted onto {} models", path, count),
+                    }
+                    if let Some(window) = self.window.as_ref() {
+                        window.window.request_redraw();
+                    }
+                    return;
+                }
+                match replace {
+                    // the model may have been removed while it was being loaded.
+                    Some(old_id) => {
+                        if !self.world.replace(old_id, id, glb) {
+                            return;
+                        }
+                    }
+                    None => self.world.add_side_by_side(id, path, glb),
+                }
+                self.update();
+                self.update_status();
+            }
+            UserEvent::ImageDecoded { id, textures, image } => {
+                let (Some(window), Some(renderer)) = (self.window.as_ref(), self.renderer.as_mut()) else {
+                    return;
+                };
+                // the model is gone if it was removed or reloaded in the meantime.
+                let Some(model) = self.world.get(id) else {
+                    return;
+                };
+                if let Err(err) = &image {
+                    eprintln!("{}", err);
+                }
+                let result = renderer.set_image(&window.device, &window.queue, model, &textures, image.as_ref().ok());
+                if let Err(err) = result {
+                    eprintln!("{}: textures {:?}: {}", model.path, textures, err);
+                }
+                window.window.request_redraw();
+            }
+            UserEvent::Changed(path) => {
+                // the placement and the rest of the state stay as they are, only the scene is swapped.
+                let mut jobs = self
+                    .world
+                    .models
+                    .iter()
+                    .filter(|m| m.path == path)
+                    .map(|m| (m.path.clone(), Some(m.id)))
+                    .collect::<Vec<_>>();
+                if let Some(i) = self.failed.iter().position(|p| *p == path) {
+                    jobs.push((self.failed.remove(i), None));
+                }
+                self.load(jobs);
+            }
+        }
+    }
 }
 
 fn main() -> Result<(), Box<dyn error::Error>> {
-    let glb = {
-        let data = fs::read(env::args().skip(1).next().ok_or("")?)?;
-        let time = time::Instant::now();
-        let glb = loader::load(io::Cursor::new(data))?;
-        println!("loader::load(): {:?}", time.elapsed());
-        glb
-    };
+    let options = options::Options::parse(env::args().skip(1))?;
+    let event_loop = event_loop::EventLoop::with_user_event().build()?;
+    if options.list_adapters {
+        list_adapters(&event_loop);
+        return Ok(());
+    }
 
-    event_loop::EventLoop::new()?.run_app(&mut App::new(glb))?;
+    let proxy = event_loop.create_proxy();
+    event_loop.run_app(&mut App::new(options, proxy))?;
 
     Ok(())
 }
@@ -0,0 +1,142 @@
+use crate::*;
+
+pub struct Options {
+    pub paths: Vec<String>,
+    pub watch: bool,
+    pub grid: bool,
+    pub axes: bool,
+    pub background: overlay::Background,
+    pub tone_mapping: post::ToneMapping,
+    pub exposure: f32,
+    pub bloom: bool,
+    pub present_mode: wgpu::PresentMode,
+    pub frame_latency: u32,
+    pub msaa: u32,
+    pub adapter: Option<String>,
+    pub list_adapters: bool,
+}
+
+const USAGE: &str = "usage: yavv [OPTIONS] FILE...
+       yavv --list-adapters
+    --watch
+    --grid
+    --axes
+    --background #rrggbb[,#rrggbb]|IMAGE
+    --tone-mapping aces|agx|reinhard|neutral
+    --exposure EV
+    --bloom
+    --present-mode vsync|mailbox|immediate
+    --frame-latency N
+    --msaa 1|2|4|8
+    --adapter INDEX|NAME";
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        f32::powf((c + 0.055) / 1.055, 2.4)
+    }
+}
+
+fn parse_color(s: &str) -> Option<[f32; 4]> {
+    let s = s.strip_prefix('#')?;
+    if s.len() != 6 {
+        return None;
+    }
+    let mut dst = [1.0; 4];
+    for i in 0..3 {
+        let c = u8::from_str_radix(s.get(2 * i..2 * i + 2)?, 16).ok()?;
+        dst[i] = srgb_to_linear(c as f32 / 255.0);
+    }
+    Some(dst)
+}
+
+fn parse_background(s: &str) -> Result<overlay::Background, Box<dyn error::Error>> {
+    if s.starts_with('#') {
+        let colors = s
+            .split(',')
+            .map(|s| parse_color(s).ok_or(format!("invalid color: {}", s)))
+            .collect::<Result<Vec<_>, _>>()?;
+        return match colors[..] {
+            [c] => Ok(overlay::Background::Color(c)),
+            [top, bottom] => Ok(overlay::Background::Gradient(top, bottom)),
+            _ => Err(format!("invalid background: {}", s).into()),
+        };
+    }
+    let image = image::open(s)?;
+    Ok(overlay::Background::Image(scene::Image::rgba8(
+        image.width(),
+        image.height(),
+        image.into_rgba8().into_vec(),
+    )))
+}
+
+impl Options {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, Box<dyn error::Error>> {
+        let mut paths = Vec::new();
+        let mut watch = false;
+        let mut grid = false;
+        let mut axes = false;
+        let mut background = overlay::Background::Color([0.0; 4]);
+        let mut tone_mapping = post::ToneMapping::Aces;
+        let mut exposure = 0.0;
+        let mut bloom = false;
+        let mut present_mode = wgpu::PresentMode::AutoVsync;
+        let mut frame_latency = 2;
+        let mut msaa = 4;
+        let mut adapter = None;
+        let mut list_adapters = false;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--watch" => watch = true,
+                "--grid" => grid = true,
+                "--axes" => axes = true,
+                "--background" => background = parse_background(&args.next().ok_or("--background: missing value")?)?,
+                "--tone-mapping" => {
+                    let name = args.next().ok_or("--tone-mapping: missing value")?;
+                    tone_mapping =
+                        post::ToneMapping::from_name(&name).ok_or(format!("unknown tone mapping: {}", name))?;
+                }
+                "--exposure" => exposure = args.next().ok_or("--exposure: missing value")?.parse()?,
+                "--bloom" => bloom = true,
+                "--present-mode" => {
+                    present_mode = match args.next().ok_or("--present-mode: missing value")?.as_str() {
+                        "vsync" => wgpu::PresentMode::AutoVsync,
+                        "mailbox" => wgpu::PresentMode::Mailbox,
+                        "immediate" => wgpu::PresentMode::Immediate,
+                        name => return Err(format!("unknown present mode: {}", name).into()),
+                    }
+                }
+                "--msaa" => {
+                    msaa = args.next().ok_or("--msaa: missing value")?.parse()?;
+                    if ![1, 2, 4, 8].contains(&msaa) {
+                        return Err(format!("invalid sample count: {}", msaa).into());
+                    }
+                }
+                "--adapter" => adapter = Some(args.next().ok_or("--adapter: missing value")?),
+                "--list-adapters" => list_adapters = true,
+                "--frame-latency" => frame_latency = args.next().ok_or("--frame-latency: missing value")?.parse()?,
+                _ if arg.starts_with("--") => return Err(format!("unknown option: {}", arg).into()),
+                _ => paths.push(arg),
+            }
+        }
+        if paths.is_empty() && !list_adapters {
+            return Err(USAGE.into());
+        }
+        Ok(Options {
+            paths: paths,
+            watch: watch,
+            grid: grid,
+            axes: axes,
+            background: background,
+            tone_mapping: tone_mapping,
+            exposure: exposure,
+            bloom: bloom,
+            present_mode: present_mode,
+            frame_latency: frame_latency,
+            msaa: msaa,
+            adapter: adapter,
+            list_adapters: list_adapters,
+        })
+    }
+}
@@ -0,0 +1,32 @@
+// in pixels.
+const WIDTH: i32 = 2;
+const COLOR = vec3(1.0, 0.5, 0.0);
+
+@group(0) @binding(0) var mask_texture: texture_2d<f32>;
+
+@vertex fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+	let position = vec2(f32(vertex_index & 1) * 4.0 - 1.0, f32(vertex_index >> 1) * 4.0 - 1.0);
+	return vec4(position, 0.0, 1.0);
+}
+
+// covers the pixels around the mask, up to WIDTH away from it.
+@fragment fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+	let size = vec2<i32>(textureDimensions(mask_texture));
+	let p = vec2<i32>(position.xy);
+	if textureLoad(mask_texture, p, 0).r > 0.0 {
+		discard;
+	}
+	var coverage = 0.0;
+	for (var y = -WIDTH; y <= WIDTH; y++) {
+		for (var x = -WIDTH; x <= WIDTH; x++) {
+			if x * x + y * y <= WIDTH * WIDTH + 1 {
+				let q = clamp(p + vec2(x, y), vec2(0), size - 1);
+				coverage = max(coverage, textureLoad(mask_texture, q, 0).r);
+			}
+		}
+	}
+	if coverage == 0.0 {
+		discard;
+	}
+	return vec4(COLOR, coverage);
+}
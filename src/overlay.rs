@@ -0,0 +1,554 @@
+use crate::*;
+use nalgebra::{Matrix4, Vector4};
+use wgpu::util::DeviceExt;
+
+// a 5x7 font for the printable ASCII characters. each glyph has 5 columns, bit 0 is the top row.
+const FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x5F, 0x00, 0x00],
+    [0x00, 0x07, 0x00, 0x07, 0x00],
+    [0x14, 0x7F, 0x14, 0x7F, 0x14],
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12],
+    [0x23, 0x13, 0x08, 0x64, 0x62],
+    [0x36, 0x49, 0x55, 0x22, 0x50],
+    [0x00, 0x05, 0x03, 0x00, 0x00],
+    [0x00, 0x1C, 0x22, 0x41, 0x00],
+    [0x00, 0x41, 0x22, 0x1C, 0x00],
+    [0x14, 0x08, 0x3E, 0x08, 0x14],
+    [0x08, 0x08, 0x3E, 0x08, 0x08],
+    [0x00, 0x50, 0x30, 0x00, 0x00],
+    [0x08, 0x08, 0x08, 0x08, 0x08],
+    [0x00, 0x60, 0x60, 0x00, 0x00],
+    [0x20, 0x10, 0x08, 0x04, 0x02],
+    [0x3E, 0x51, 0x49, 0x45, 0x3E],
+    [0x00, 0x42, 0x7F, 0x40, 0x00],
+    [0x42, 0x61, 0x51, 0x49, 0x46],
+    [0x21, 0x41, 0x45, 0x4B, 0x31],
+    [0x18, 0x14, 0x12, 0x7F, 0x10],
+    [0x27, 0x45, 0x45, 0x45, 0x39],
+    [0x3C, 0x4A, 0x49, 0x49, 0x30],
+    [0x01, 0x71, 0x09, 0x05, 0x03],
+    [0x36, 0x49, 0x49, 0x49, 0x36],
+    [0x06, 0x49, 0x49, 0x29, 0x1E],
+    [0x00, 0x36, 0x36, 0x00, 0x00],
+    [0x00, 0x56, 0x36, 0x00, 0x00],
+    [0x08, 0x14, 0x22, 0x41, 0x00],
+    [0x14, 0x14, 0x14, 0x14, 0x14],
+    [0x00, 0x41, 0x22, 0x14, 0x08],
+    [0x02, 0x01, 0x51, 0x09, 0x06],
+    [0x32, 0x49, 0x79, 0x41, 0x3E],
+    [0x7E, 0x11, 0x11, 0x11, 0x7E],
+    [0x7F, 0x49, 0x49, 0x49, 0x36],
+    [0x3E, 0x41, 0x41, 0x41, 0x22],
+    [0x7F, 0x41, 0x41, 0x22, 0x1C],
+    [0x7F, 0x49, 0x49, 0x49, 0x41],
+    [0x7F, 0x09, 0x09, 0x09, 0x01],
+    [0x3E, 0x41, 0x49, 0x49, 0x7A],
+    [0x7F, 0x08, 0x08, 0x08, 0x7F],
+    [0x00, 0x41, 0x7F, 0x41, 0x00],
+    [0x20, 0x40, 0x41, 0x3F, 0x01],
+    [0x7F, 0x08, 0x14, 0x22, 0x41],
+    [0x7F, 0x40, 0x40, 0x40, 0x40],
+    [0x7F, 0x02, 0x0C, 0x02, 0x7F],
+    [0x7F, 0x04, 0x08, 0x10, 0x7F],
+    [0x3E, 0x41, 0x41, 0x41, 0x3E],
+    [0x7F, 0x09, 0x09, 0x09, 0x06],
+    [0x3E, 0x41, 0x51, 0x21, 0x5E],
+    [0x7F, 0x09, 0x19, 0x29, 0x46],
+    [0x46, 0x49, 0x49, 0x49, 0x31],
+    [0x01, 0x01, 0x7F, 0x01, 0x01],
+    [0x3F, 0x40, 0x40, 0x40, 0x3F],
+    [0x1F, 0x20, 0x40, 0x20, 0x1F],
+    [0x3F, 0x40, 0x38, 0x40, 0x3F],
+    [0x63, 0x14, 0x08, 0x14, 0x63],
+    [0x07, 0x08, 0x70, 0x08, 0x07],
+    [0x61, 0x51, 0x49, 0x45, 0x43],
+    [0x00, 0x7F, 0x41, 0x41, 0x00],
+    [0x02, 0x04, 0x08, 0x10, 0x20],
+    [0x00, 0x41, 0x41, 0x7F, 0x00],
+    [0x04, 0x02, 0x01, 0x02, 0x04],
+    [0x40, 0x40, 0x40, 0x40, 0x40],
+    [0x00, 0x01, 0x02, 0x04, 0x00],
+    [0x20, 0x54, 0x54, 0x54, 0x78],
+    [0x7F, 0x48, 0x44, 0x44, 0x38],
+    [0x38, 0x44, 0x44, 0x44, 0x20],
+    [0x38, 0x44, 0x44, 0x48, 0x7F],
+    [0x38, 0x54, 0x54, 0x54, 0x18],
+    [0x08, 0x7E, 0x09, 0x01, 0x02],
+    [0x0C, 0x52, 0x52, 0x52, 0x3E],
+    [0x7F, 0x08, 0x04, 0x04, 0x78],
+    [0x00, 0x44, 0x7D, 0x40, 0x00],
+    [0x20, 0x40, 0x44, 0x3D, 0x00],
+    [0x7F, 0x10, 0x28, 0x44, 0x00],
+    [0x00, 0x41, 0x7F, 0x40, 0x00],
+    [0x7C, 0x04, 0x18, 0x04, 0x78],
+    [0x7C, 0x08, 0x04, 0x04, 0x78],
+    [0x38, 0x44, 0x44, 0x44, 0x38],
+    [0x7C, 0x14, 0x14, 0x14, 0x08],
+    [0x08, 0x14, 0x14, 0x18, 0x7C],
+    [0x7C, 0x08, 0x04, 0x04, 0x08],
+    [0x48, 0x54, 0x54, 0x54, 0x20],
+    [0x04, 0x3F, 0x44, 0x40, 0x20],
+    [0x3C, 0x40, 0x40, 0x20, 0x7C],
+    [0x1C, 0x20, 0x40, 0x20, 0x1C],
+    [0x3C, 0x40, 0x30, 0x40, 0x3C],
+    [0x44, 0x28, 0x10, 0x28, 0x44],
+    [0x0C, 0x50, 0x50, 0x50, 0x3C],
+    [0x44, 0x64, 0x54, 0x4C, 0x44],
+    [0x00, 0x08, 0x36, 0x41, 0x00],
+    [0x00, 0x00, 0x7F, 0x00, 0x00],
+    [0x00, 0x41, 0x36, 0x08, 0x00],
+    [0x08, 0x04, 0x08, 0x10, 0x08],
+];
+// the glyphs are scaled up by an integer factor in cells of 6x9 font pixels.
+const FONT_SCALE: usize = 2;
+
+pub enum Background {
+    Color([f32; 4]),
+    Gradient([f32; 4], [f32; 4]),
+    Image(scene::Image),
+}
+
+#[repr(C, align(16))]
+struct OverlayUniform {
+    m_camera: [[f32; 4]; 4],
+    m_view: [[f32; 4]; 4],
+    projection_scale: [f32; 4],
+    color_top: [f32; 4],
+    color_bottom: [f32; 4],
+    background_mode: u32,
+}
+
+pub struct Overlay {
+    pub grid: bool,
+    pub axes: bool,
+    background: Background,
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    uniform: wgpu::Buffer,
+    group: wgpu::BindGroup,
+    format: wgpu::TextureFormat,
+    pipeline_layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    background_pipeline: wgpu::RenderPipeline,
+    grid_pipeline: wgpu::RenderPipeline,
+    axes_pipeline: wgpu::RenderPipeline,
+    // the status text is drawn over the final image, so that tone mapping and bloom leave it alone.
+    text_layout: wgpu::BindGroupLayout,
+    text_pipeline: wgpu::RenderPipeline,
+    text_group: Option<wgpu::BindGroup>,
+}
+
+impl Overlay {
+    pub const AXES_SIZE: u32 = 96;
+
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        target_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: None,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let uniform = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: mem::size_of::<OverlayUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[Some(&layout)],
+            immediate_size: 0,
+        });
+        let shader = device.create_shader_module(wgpu::include_wgsl!("overlay.wgsl"));
+        let [background_pipeline, grid_pipeline, axes_pipeline] =
+            Self::create_pipelines(device, &pipeline_layout, &shader, format, sample_count);
+
+        let text_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            }],
+            label: None,
+        });
+        let text_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[Some(&text_layout)],
+                immediate_size: 0,
+            })),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_fullscreen"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_text"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: Default::default(),
+            depth_stencil: None,
+            multisample: Default::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let placeholder = scene::Image::rgba8(1, 1, vec![0; 4]);
+        let group = Self::create_group(device, queue, &layout, &uniform, &sampler, &placeholder);
+
+        Overlay {
+            grid: false,
+            axes: false,
+            background: Background::Color([0.0; 4]),
+            layout: layout,
+            sampler: sampler,
+            uniform: uniform,
+            group: group,
+            format: format,
+            pipeline_layout: pipeline_layout,
+            shader: shader,
+            background_pipeline: background_pipeline,
+            grid_pipeline: grid_pipeline,
+            axes_pipeline: axes_pipeline,
+            text_layout: text_layout,
+            text_pipeline: text_pipeline,
+            text_group: None,
+        }
+    }
+
+    fn create_pipelines(
+        device: &wgpu::Device,
+        pipeline_layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> [wgpu::RenderPipeline; 3] {
+        let create_pipeline = |vs: &str, fs: &str, topology, blend, depth_compare| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: None,
+                layout: Some(pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: Some(vs),
+                    compilation_options: Default::default(),
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: Some(fs),
+                    compilation_options: Default::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: format,
+                        blend: blend,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: topology,
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: Some(false),
+                    depth_compare: Some(depth_compare),
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview_mask: None,
+                cache: None,
+            })
+        };
+        [
+            create_pipeline(
+                "vs_fullscreen",
+                "fs_background",
+                wgpu::PrimitiveTopology::TriangleList,
+                None,
+                wgpu::CompareFunction::Always,
+            ),
+            create_pipeline(
+                "vs_fullscreen",
+                "fs_grid",
+                wgpu::PrimitiveTopology::TriangleList,
+                Some(wgpu::BlendState::ALPHA_BLENDING),
+                wgpu::CompareFunction::Greater,
+            ),
+            create_pipeline(
+                "vs_axes",
+                "fs_axes",
+                wgpu::PrimitiveTopology::LineList,
+                None,
+                wgpu::CompareFunction::Always,
+            ),
+        ]
+    }
+
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        [self.background_pipeline, self.grid_pipeline, self.axes_pipeline] =
+            Self::create_pipelines(device, &self.pipeline_layout, &self.shader, self.format, sample_count);
+    }
+
+    fn create_group(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        uniform: &wgpu::Buffer,
+        sampler: &wgpu::Sampler,
+        image: &scene::Image,
+    ) -> wgpu::BindGroup {
+        let texture = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                size: wgpu::Extent3d {
+                    width: image.dims[0],
+                    height: image.dims[1],
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                label: None,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            &image.buffer,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+            label: None,
+        })
+    }
+
+    pub fn set_background(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, background: Background) {
+        if let Background::Image(image) = &background {
+            self.group = Self::create_group(device, queue, &self.layout, &self.uniform, &self.sampler, image);
+        }
+        self.background = background;
+    }
+
+    pub fn clear_color(&self) -> wgpu::Color {
+        match self.background {
+            Background::Color(c) => wgpu::Color {
+                r: c[0] as f64,
+                g: c[1] as f64,
+                b: c[2] as f64,
+                a: c[3] as f64,
+            },
+            _ => wgpu::Color::TRANSPARENT,
+        }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, camera: &Matrix4<f32>, projection_scale: &Vector4<f32>) {
+        let (color_top, color_bottom, background_mode) = match self.background {
+            Background::Color(c) => (c, c, 0),
+            Background::Gradient(top, bottom) => (top, bottom, 1),
+            Background::Image(_) => ([0.0; 4], [0.0; 4], 2),
+        };
+        let uniform = OverlayUniform {
+            m_camera: *camera.as_ref(),
+            m_view: *camera.try_inverse().unwrap().as_ref(),
+            projection_scale: *projection_scale.as_ref(),
+            color_top: color_top,
+            color_bottom: color_bottom,
+            background_mode: background_mode,
+        };
+        queue.write_buffer(&self.uniform, 0, unsafe { utils::as_bytes(&uniform) });
+    }
+
+    pub fn draw_background<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
+        if let Background::Color(_) = self.background {
+            return;
+        }
+        pass.set_pipeline(&self.background_pipeline);
+        pass.set_bind_group(0, &self.group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+
+    pub fn draw_grid<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
+        if !self.grid {
+            return;
+        }
+        pass.set_pipeline(&self.grid_pipeline);
+        pass.set_bind_group(0, &self.group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+
+    pub fn draw_axes<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, w: u32, h: u32) {
+        if !self.axes {
+            return;
+        }
+        let size = Self::AXES_SIZE.min(w).min(h);
+        pass.set_viewport(0.0, (h - size) as f32, size as f32, size as f32, 0.0, 1.0);
+        pass.set_pipeline(&self.axes_pipeline);
+        pass.set_bind_group(0, &self.group, &[]);
+        pass.draw(0..6, 0..1);
+        pass.set_viewport(0.0, 0.0, w as f32, h as f32, 0.0, 1.0);
+    }
+
+    // replaces the status text. an empty text hides it.
+    pub fn set_text(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, text: &str) {
+        if text.is_empty() {
+            self.text_group = None;
+            return;
+        }
+        let image = rasterize(text);
+        let texture = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                size: wgpu::Extent3d {
+                    width: image.dims[0],
+                    height: image.dims[1],
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                label: None,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            &image.buffer,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        self.text_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.text_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(&view),
+            }],
+            label: None,
+        }));
+    }
+
+    pub fn draw_text(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let Some(group) = &self.text_group else {
+            return;
+        };
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: view,
+                depth_slice: None,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+        pass.set_pipeline(&self.text_pipeline);
+        pass.set_bind_group(0, group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+// renders the lines of the text in white on a translucent box. the characters outside of ASCII become "?".
+fn rasterize(text: &str) -> scene::Image {
+    let lines = text.lines().collect::<Vec<_>>();
+    let columns = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let (w, h) = (6 * columns + 3, 9 * lines.len() + 2);
+    let mut pixels = vec![false; w * h];
+    for (row, line) in lines.iter().enumerate() {
+        for (column, c) in line.chars().enumerate() {
+            let c = if (' '..='~').contains(&c) { c } else { '?' };
+            for (x, bits) in FONT[c as usize - 32].iter().enumerate() {
+                for y in 0..7 {
+                    if bits >> y & 1 != 0 {
+                        pixels[(2 + 9 * row + y) * w + 2 + 6 * column + x] = true;
+                    }
+                }
+            }
+        }
+    }
+
+    let (width, height) = (w * FONT_SCALE, h * FONT_SCALE);
+    let mut buffer = Vec::with_capacity(4 * width * height);
+    for y in 0..height {
+        for x in 0..width {
+            match pixels[y / FONT_SCALE * w + x / FONT_SCALE] {
+                true => buffer.extend([255, 255, 255, 255]),
+                false => buffer.extend([0, 0, 0, 160]),
+            }
+        }
+    }
+    scene::Image::rgba8(width as u32, height as u32, buffer)
+}
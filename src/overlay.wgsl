@@ -0,0 +1,101 @@
+struct Overlay {
+	m_camera: mat4x4<f32>,
+	m_view: mat4x4<f32>,
+	projection_scale: vec4<f32>,
+	color_top: vec4<f32>,
+	color_bottom: vec4<f32>,
+	background_mode: u32,
+}
+
+struct VertexToFragment {
+	@builtin(position) builtin_position: vec4<f32>,
+	@location(0) position: vec2<f32>,
+	@location(1) color: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> overlay: Overlay;
+@group(0) @binding(1) var background_texture: texture_2d<f32>;
+@group(0) @binding(2) var background_sampler: sampler;
+@group(0) @binding(3) var text_texture: texture_2d<f32>;
+
+const PI: f32 = 3.14159265358979;
+// the distance of the status text from the top left corner, in pixels.
+const TEXT_MARGIN: i32 = 8;
+
+fn view_ray(position: vec2<f32>) -> vec3<f32> {
+	let s = overlay.projection_scale;
+	let dir = vec3(position.x * s.z / s.x, position.y * s.z / s.y, 1.0);
+	return (overlay.m_camera * vec4(dir, 0.0)).xyz;
+}
+
+@vertex fn vs_fullscreen(@builtin(vertex_index) vertex_index: u32) -> VertexToFragment {
+	let position = vec2(f32(vertex_index & 1) * 4.0 - 1.0, f32(vertex_index >> 1) * 4.0 - 1.0);
+	var vtf: VertexToFragment;
+	vtf.builtin_position = vec4(position, 0.0, 1.0);
+	vtf.position = position;
+	return vtf;
+}
+
+@fragment fn fs_background(vtf: VertexToFragment) -> @location(0) vec4<f32> {
+	if overlay.background_mode == 1 {
+		return mix(overlay.color_bottom, overlay.color_top, vtf.position.y * 0.5 + 0.5);
+	}
+	let dir = normalize(view_ray(vtf.position));
+	let uv = vec2(atan2(dir.x, -dir.z) / (2.0 * PI) + 0.5, acos(clamp(dir.y, -1.0, 1.0)) / PI);
+	return textureSampleLevel(background_texture, background_sampler, uv, 0.0);
+}
+
+fn grid_line(coord: vec2<f32>, spacing: f32) -> f32 {
+	let c = coord / spacing;
+	let d = abs(fract(c - 0.5) - 0.5) / fwidth(c);
+	return 1.0 - min(min(d.x, d.y), 1.0);
+}
+
+struct GridOutput {
+	@location(0) color: vec4<f32>,
+	@builtin(frag_depth) depth: f32,
+}
+
+@fragment fn fs_grid(vtf: VertexToFragment) -> GridOutput {
+	let origin = overlay.m_camera[3].xyz;
+	let dir = view_ray(vtf.position);
+	let t = -origin.y / dir.y;
+	let hit = origin + t * dir;
+
+	let major = grid_line(hit.xz, 1.0);
+	let minor = grid_line(hit.xz, 0.1) * (1.0 - smoothstep(0.02, 0.1, length(fwidth(hit.xz))));
+	let axis = 1.0 - min(abs(hit.xz) / fwidth(hit.xz), vec2(1.0));
+	var color = vec4(vec3(0.5), max(major * 0.6, minor * 0.3));
+	color = mix(color, vec4(0.2, 0.2, 1.0, 1.0), axis.x);
+	color = mix(color, vec4(1.0, 0.2, 0.2, 1.0), axis.y);
+	color.a *= 1.0 - smoothstep(20.0, 100.0, t);
+
+	if t <= 0.0 || color.a <= 0.0 {
+		discard;
+	}
+	var out: GridOutput;
+	out.color = color;
+	out.depth = overlay.projection_scale.w / (t * overlay.projection_scale.z);
+	return out;
+}
+
+@vertex fn vs_axes(@builtin(vertex_index) vertex_index: u32) -> VertexToFragment {
+	let axis = vec3(f32(vertex_index / 2 == 0), f32(vertex_index / 2 == 1), f32(vertex_index / 2 == 2));
+	let dir = normalize((overlay.m_view * vec4(axis, 0.0)).xyz);
+	var vtf: VertexToFragment;
+	vtf.builtin_position = vec4(dir.xy * 0.8 * f32(vertex_index & 1), 0.5, 1.0);
+	vtf.color = vec4(axis, 1.0);
+	return vtf;
+}
+
+@fragment fn fs_axes(vtf: VertexToFragment) -> @location(0) vec4<f32> {
+	return vtf.color;
+}
+
+@fragment fn fs_text(vtf: VertexToFragment) -> @location(0) vec4<f32> {
+	let p = vec2<i32>(vtf.builtin_position.xy) - TEXT_MARGIN;
+	if any(p < vec2(0)) || any(p >= vec2<i32>(textureDimensions(text_texture))) {
+		discard;
+	}
+	return textureLoad(text_texture, p, 0);
+}
@@ -0,0 +1,572 @@
+use crate::*;
+use nalgebra::{Matrix4, Point3, Vector3};
+
+// the points origin + dir * t for t >= 0. the direction need not be normalized, so that the distances stay the same
+// in the spaces of all the nodes.
+#[derive(Clone, Copy, Debug)]
+pub struct Ray {
+    pub origin: Point3<f32>,
+    pub dir: Vector3<f32>,
+}
+
+// the vertices of a primitive as the CPU needs them for picking. they are copied at load time, as the blob is
+// released after the upload, and the trees are built on the first pick.
+#[derive(Debug, Default)]
+pub struct Geometry {
+    pub positions: Vec<Vector3<f32>>,
+    pub triangles: Vec<[u32; 3]>,
+    // empty for the primitives without a skin.
+    pub joints: Vec<[u32; 4]>,
+    pub weights: Vec<[f32; 4]>,
+    // the position deltas of the morph targets, empty for the targets which do not move the positions.
+    pub deltas: Vec<Vec<Vector3<f32>>>,
+    // the triangles left in the first person view, for the primitives which have head-less indices.
+    head_less: Option<Vec<u32>>,
+    // over all the triangles and over the head-less ones.
+    bvhs: sync::OnceLock<(Bvh, Option<Bvh>)>,
+}
+
+// a bounding volume hierarchy over the triangles. the first child of an inner node follows it.
+#[derive(Clone, Debug, Default)]
+struct Bvh {
+    nodes: Vec<BvhNode>,
+    triangles: Vec<u32>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct BvhNode {
+    aabb: scene::Aabb,
+    // the range in Bvh::triangles for the leaves. the inner nodes have no triangles and start at their second child.
+    start: u32,
+    count: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    pub node: usize,
+    pub mesh: usize,
+    pub primitive: usize,
+    pub triangle: usize,
+    // the indices of the vertices of the triangle, and the weights of the vertices at the hit point.
+    pub vertices: [u32; 3],
+    pub barycentric: Vector3<f32>,
+    // in units of the direction of the ray.
+    pub distance: f32,
+}
+
+impl Ray {
+    pub fn at(&self, t: f32) -> Point3<f32> {
+        self.origin + self.dir * t
+    }
+
+    fn transform(&self, m: &Matrix4<f32>) -> Ray {
+        Ray {
+            origin: m.transform_point(&self.origin),
+            dir: m.transform_vector(&self.dir),
+        }
+    }
+
+    // whether the ray enters the box before "far".
+    fn enters(&self, aabb: &scene::Aabb, inv_dir: &Vector3<f32>, far: f32) -> bool {
+        let mut near = 0.0;
+        let mut far = far;
+        for i in 0..3 {
+            let t0 = (aabb.min[i] - self.origin[i]) * inv_dir[i];
+            let t1 = (aabb.max[i] - self.origin[i]) * inv_dir[i];
+            // max() and min() skip the NaNs of a ray which runs along a face.
+            near = f32::max(near, f32::min(t0, t1));
+            far = f32::min(far, f32::max(t0, t1));
+        }
+        near <= far
+    }
+
+    // Möller-Trumbore. both sides of the triangle are hit.
+    fn intersect(&self, [a, b, c]: [&Vector3<f32>; 3]) -> Option<(Vector3<f32>, f32)> {
+        let e1 = b - a;
+        let e2 = c - a;
+        let p = self.dir.cross(&e2);
+        let det = e1.dot(&p);
+        if det == 0.0 {
+            return None;
+        }
+        let s = self.origin.coords - a;
+        let u = s.dot(&p) / det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(&e1);
+        let v = self.dir.dot(&q) / det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = e2.dot(&q) / det;
+        (t >= 0.0).then(|| (Vector3::new(1.0 - u - v, u, v), t))
+    }
+}
+
+impl Bvh {
+    const LEAF_SIZE: usize = 4;
+
+    // over the triangles in "subset", which are indices into "triangles".
+    fn new(positions: &[Vector3<f32>], triangles: &[[u32; 3]], subset: Vec<u32>) -> Self {
+        let centroids = triangles
+            .iter()
+            .map(|t| t.iter().map(|v| positions[*v as usize]).sum::<Vector3<f32>>() / 3.0)
+            .collect::<Vec<_>>();
+        let count = subset.len();
+        let mut bvh = Bvh {
+            nodes: Vec::new(),
+            triangles: subset,
+        };
+        if count > 0 {
+            bvh.split(positions, triangles, &centroids, 0, count);
+        }
+        bvh
+    }
+
+    fn bounds(&self, positions: &[Vector3<f32>], triangles: &[[u32; 3]], start: usize, count: usize) -> scene::Aabb {
+        let mut aabb = scene::Aabb {
+            min: Vector3::repeat(f32::INFINITY),
+            max: Vector3::repeat(f32::NEG_INFINITY),
+        };
+        for t in self.triangles[start..start + count].iter() {
+            for v in triangles[*t as usize].iter() {
+                aabb.min = aabb.min.inf(&positions[*v as usize]);
+                aabb.max = aabb.max.sup(&positions[*v as usize]);
+            }
+        }
+        aabb
+    }
+
+    // splits the triangles at the median of their centroids along the longest side of their bounds.
+    fn split(
+        &mut self,
+        positions: &[Vector3<f32>],
+        triangles: &[[u32; 3]],
+        centroids: &[Vector3<f32>],
+        start: usize,
+        end: usize,
+    ) {
+        let n = self.nodes.len();
+        let aabb = self.bounds(positions, triangles, start, end - start);
+        self.nodes.push(BvhNode {
+            aabb: aabb,
+            start: start as u32,
+            count: (end - start) as u32,
+        });
+        if end - start <= Self::LEAF_SIZE {
+            return;
+        }
+        let axis = (aabb.max - aabb.min).imax();
+        let mid = (start + end) / 2;
+        self.triangles[start..end].select_nth_unstable_by(mid - start, |a, b| {
+            centroids[*a as usize][axis].total_cmp(&centroids[*b as usize][axis])
+        });
+        self.split(positions, triangles, centroids, start, mid);
+        self.nodes[n].start = self.nodes.len() as u32;
+        self.nodes[n].count = 0;
+        self.split(positions, triangles, centroids, mid, end);
+    }
+
+    // recomputes the bounds for moved vertices. the tree stays as it was built, which keeps it correct if slower.
+    fn refit(&mut self, positions: &[Vector3<f32>], triangles: &[[u32; 3]]) {
+        // the children come after their parents.
+        for i in (0..self.nodes.len()).rev() {
+            let node = self.nodes[i];
+            self.nodes[i].aabb = match node.count {
+                0 => self.nodes[i + 1].aabb.union(&self.nodes[node.start as usize].aabb),
+                n => self.bounds(positions, triangles, node.start as usize, n as usize),
+            };
+        }
+    }
+
+    // the closest triangle along the ray, with the barycentric coordinates and the distance of the hit point.
+    fn cast(
+        &self,
+        positions: &[Vector3<f32>],
+        triangles: &[[u32; 3]],
+        ray: &Ray,
+    ) -> Option<(usize, Vector3<f32>, f32)> {
+        let inv_dir = ray.dir.map(|d| 1.0 / d);
+        let mut closest: Option<(usize, Vector3<f32>, f32)> = None;
+        let mut stack = if self.nodes.is_empty() { Vec::new() } else { vec![0] };
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+            if !ray.enters(&node.aabb, &inv_dir, closest.map_or(f32::INFINITY, |c| c.2)) {
+                continue;
+            }
+            if node.count == 0 {
+                stack.push(node.start as usize);
+                stack.push(i + 1);
+                continue;
+            }
+            for t in self.triangles[node.start as usize..(node.start + node.count) as usize].iter() {
+                let vertices = triangles[*t as usize].map(|v| &positions[v as usize]);
+                if let Some((barycentric, distance)) = ray.intersect(vertices)
+                    && closest.is_none_or(|c| distance < c.2)
+                {
+                    closest = Some((*t as usize, barycentric, distance));
+                }
+            }
+        }
+        closest
+    }
+}
+
+impl Geometry {
+    // the vertices of a triangle list. the joints and the weights are empty or four per vertex, as are the deltas.
+    // "head_less" are the indices of the first person variant, a part of the triangles in the same order.
+    pub fn new(
+        positions: &[f32],
+        indices: Option<&[f32]>,
+        head_less: Option<&[u32]>,
+        joints: &[f32],
+        weights: &[f32],
+        deltas: &[Vec<f32>],
+    ) -> Self {
+        let vec3s = |v: &[f32]| v.chunks_exact(3).map(Vector3::from_column_slice).collect::<Vec<_>>();
+        let positions = vec3s(positions);
+        let valid = |t: &[u32; 3]| t.iter().all(|v| (*v as usize) < positions.len());
+        let triangles: Vec<[u32; 3]> = match indices {
+            Some(indices) => indices
+                .chunks_exact(3)
+                .map(|t| [t[0] as u32, t[1] as u32, t[2] as u32])
+                .filter(valid)
+                .collect(),
+            None => (0..positions.len() as u32 / 3)
+                .map(|t| [t * 3, t * 3 + 1, t * 3 + 2])
+                .collect(),
+        };
+        let head_less = head_less.map(|indices| {
+            let mut kept = (indices.chunks_exact(3))
+                .map(|t| [t[0], t[1], t[2]])
+                .filter(valid)
+                .peekable();
+            (0..triangles.len() as u32)
+                .filter(|t| kept.next_if_eq(&triangles[*t as usize]).is_some())
+                .collect()
+        });
+        let skinned = joints.len() == positions.len() * 4 && weights.len() == positions.len() * 4;
+        let (joints, weights) = match skinned {
+            true => (
+                joints
+                    .chunks_exact(4)
+                    .map(|j| [j[0], j[1], j[2], j[3]].map(|j| j as u32))
+                    .collect(),
+                weights.chunks_exact(4).map(|w| [w[0], w[1], w[2], w[3]]).collect(),
+            ),
+            false => (Vec::new(), Vec::new()),
+        };
+        let deltas = deltas
+            .iter()
+            .map(|d| match d.len() == positions.len() * 3 {
+                true => vec3s(d),
+                false => Vec::new(),
+            })
+            .collect();
+        Geometry {
+            positions: positions,
+            triangles: triangles,
+            joints: joints,
+            weights: weights,
+            deltas: deltas,
+            head_less: head_less,
+            bvhs: sync::OnceLock::new(),
+        }
+    }
+
+    fn bvhs(&self) -> &(Bvh, Option<Bvh>) {
+        self.bvhs.get_or_init(|| {
+            let all = (0..self.triangles.len() as u32).collect();
+            let head_less = (self.head_less.clone()).map(|t| Bvh::new(&self.positions, &self.triangles, t));
+            (Bvh::new(&self.positions, &self.triangles, all), head_less)
+        })
+    }
+
+    // the positions after morphing and skinning, as the vertex shader computes them, or None if they do not move.
+    pub fn deform(&self, joints: &[Matrix4<f32>], morph_weights: &[f32]) -> Option<Vec<Vector3<f32>>> {
+        let targets = (self.deltas.iter().zip(morph_weights))
+            .filter(|(d, w)| !d.is_empty() && **w != 0.0)
+            .collect::<Vec<_>>();
+        let skinned = !self.joints.is_empty() && !joints.is_empty();
+        if targets.is_empty() && !skinned {
+            return None;
+        }
+        let mut positions = self.positions.clone();
+        for (deltas, weight) in targets {
+            for (p, d) in positions.iter_mut().zip(deltas) {
+                *p += d * *weight;
+            }
+        }
+        if skinned {
+            for (i, p) in positions.iter_mut().enumerate() {
+                // the vertices without a skin have zero weights.
+                let w = &self.weights[i];
+                if w.iter().sum::<f32>() <= 0.0 {
+                    continue;
+                }
+                let m = (0..4)
+                    .map(|k| {
+                        joints
+                            .get(self.joints[i][k] as usize)
+                            .map_or(Matrix4::zeros(), |j| j * w[k])
+                    })
+                    .sum::<Matrix4<f32>>();
+                *p = m.transform_point(&Point3::from(*p)).coords;
+            }
+        }
+        Some(positions)
+    }
+
+    // the closest triangle along a ray in the space of the node, with the positions deformed as for rendering.
+    // "head_less" tests only the triangles of the first person variant, if there is one.
+    fn cast(
+        &self,
+        ray: &Ray,
+        joints: &[Matrix4<f32>],
+        morph_weights: &[f32],
+        head_less: bool,
+    ) -> Option<(usize, Vector3<f32>, f32)> {
+        let bvh = match (head_less, self.bvhs()) {
+            (true, (_, Some(bvh))) => bvh,
+            (_, (bvh, _)) => bvh,
+        };
+        match self.deform(joints, morph_weights) {
+            None => bvh.cast(&self.positions, &self.triangles, ray),
+            Some(positions) => {
+                let mut bvh = bvh.clone();
+                bvh.refit(&positions, &self.triangles);
+                bvh.cast(&positions, &self.triangles, ray)
+            }
+        }
+    }
+}
+
+impl Hit {
+    // the vertex closest to the hit point.
+    pub fn vertex(&self) -> u32 {
+        self.vertices[self.barycentric.imax()]
+    }
+}
+
+// the GPU only gets the vertices, so those of all the primitives are copied from the blob.
+pub fn build(glb: &scene::Glb, blob: &[u8]) -> Vec<Vec<Option<Geometry>>> {
+    let read = |index: Option<usize>, normalized| {
+        let accessor = &glb.accessors[index?];
+        Some(accessor.read(&glb.views[accessor.view], blob, normalized))
+    };
+    let build = |primitive: &scene::Primitive| {
+        let positions = read(primitive.attributes.position, false)?;
+        let indices = read(primitive.indices, false);
+        let joints = read(primitive.attributes.joints_0, false).unwrap_or_default();
+        let weights = read(primitive.attributes.weights_0, true).unwrap_or_default();
+        let deltas = (primitive.targets.iter())
+            .map(|t| read(t.position, true).unwrap_or_default())
+            .collect::<Vec<_>>();
+        let head_less = primitive.head_less_indices.as_deref();
+        Some(Geometry::new(
+            &positions,
+            indices.as_deref(),
+            head_less,
+            &joints,
+            &weights,
+            &deltas,
+        ))
+    };
+    glb.meshes
+        .iter()
+        .map(|mesh| mesh.primitives.iter().map(build).collect())
+        .collect()
+}
+
+// the closest triangle of a scene along a ray. "transform" places the scene, and the meshes which the first person
+// view hides, or which only it shows, are skipped depending on "first_person".
+pub fn cast(glb: &scene::Glb, transform: &Matrix4<f32>, ray: &Ray, first_person: bool) -> Option<Hit> {
+    let worlds = glb.world_transforms();
+    let mut closest: Option<Hit> = None;
+    for (n, node) in glb.nodes.iter().enumerate() {
+        let scene::Element::Mesh(mesh) = node.element else {
+            continue;
+        };
+        let hidden = match node.first_person {
+            scene::FirstPerson::ThirdPersonOnly => first_person,
+            scene::FirstPerson::FirstPersonOnly => !first_person,
+            _ => false,
+        };
+        if hidden {
+            continue;
+        }
+        let head_less = first_person && node.first_person == scene::FirstPerson::Auto;
+        let Some(inverse) = (transform * worlds[n]).try_inverse() else {
+            continue;
+        };
+        let local = ray.transform(&inverse);
+        // the joint matrices relative to the node, as the renderer has them.
+        let joints = match node.skin {
+            Some(skin) => {
+                let skin = &glb.skins[skin];
+                let inverse = worlds[n].try_inverse().unwrap_or_else(Matrix4::identity);
+                (skin.joints.iter().zip(skin.inverse_bind_matrices.iter()))
+                    .map(|(j, inverse_bind)| inverse * worlds[*j] * inverse_bind)
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+        let mesh_weights = glb.meshes[mesh].weights.as_deref().unwrap_or(&[]);
+        for (p, geometry) in glb.geometry[mesh].iter().enumerate() {
+            let Some(geometry) = geometry else {
+                continue;
+            };
+            let Some((triangle, barycentric, distance)) = geometry.cast(&local, &joints, mesh_weights, head_less)
+            else {
+                continue;
+            };
+            if closest.is_none_or(|c| distance < c.distance) {
+                closest = Some(Hit {
+                    node: n,
+                    mesh: mesh,
+                    primitive: p,
+                    triangle: triangle,
+                    vertices: geometry.triangles[triangle],
+                    barycentric: barycentric,
+                    distance: distance,
+                });
+            }
+        }
+    }
+    closest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ray(origin: [f32; 3], dir: [f32; 3]) -> Ray {
+        Ray {
+            origin: Point3::from(origin),
+            dir: Vector3::from(dir),
+        }
+    }
+
+    // a grid of n by n quads in the xy plane, from 0 to 1, at z = 0.
+    fn grid(n: usize) -> (Vec<f32>, Vec<f32>) {
+        let positions = (0..=n)
+            .flat_map(|y| (0..=n).map(move |x| [x as f32 / n as f32, y as f32 / n as f32, 0.0]))
+            .flatten()
+            .collect();
+        let indices = (0..n)
+            .flat_map(|y| (0..n).map(move |x| (y * (n + 1) + x) as f32))
+            .flat_map(|v| {
+                let w = (n + 1) as f32;
+                [v, v + 1.0, v + w, v + 1.0, v + w + 1.0, v + w]
+            })
+            .collect();
+        (positions, indices)
+    }
+
+    #[test]
+    fn finds_the_barycentric_coordinates() {
+        let (a, b, c) = (Vector3::zeros(), Vector3::x(), Vector3::y());
+        let (barycentric, t) = ray([0.25, 0.5, 2.0], [0.0, 0.0, -2.0]).intersect([&a, &b, &c]).unwrap();
+        assert!(
+            (barycentric - Vector3::new(0.25, 0.25, 0.5)).norm() < 1e-6,
+            "{}",
+            barycentric
+        );
+        assert_eq!(t, 1.0);
+        assert!(
+            ray([0.75, 0.5, 2.0], [0.0, 0.0, -1.0])
+                .intersect([&a, &b, &c])
+                .is_none()
+        );
+        assert!(ray([0.25, 0.5, 2.0], [0.0, 0.0, 1.0]).intersect([&a, &b, &c]).is_none());
+    }
+
+    #[test]
+    fn tests_the_head_less_triangles_in_the_first_person_view() {
+        let (positions, indices) = grid(1);
+        let geometry = Geometry::new(&positions, Some(&indices), Some(&[1, 3, 2]), &[], &[], &[]);
+        let lower = ray([0.2, 0.2, 1.0], [0.0, 0.0, -1.0]);
+        let upper = ray([0.8, 0.8, 1.0], [0.0, 0.0, -1.0]);
+        assert_eq!(geometry.cast(&lower, &[], &[], false).map(|h| h.0), Some(0));
+        assert_eq!(geometry.cast(&lower, &[], &[], true), None);
+        assert_eq!(geometry.cast(&upper, &[], &[], true).map(|h| h.0), Some(1));
+    }
+
+    #[test]
+    fn agrees_with_testing_every_triangle() {
+        let (positions, indices) = grid(8);
+        let geometry = Geometry::new(&positions, Some(&indices), None, &[], &[], &[]);
+        let (bvh, _) = geometry.bvhs();
+        assert!(bvh.nodes.len() > 1);
+        for i in 0..50 {
+            let (x, y) = ((i * 7 % 50) as f32 / 49.0, (i * 13 % 50) as f32 / 49.0);
+            let r = ray([0.5, 0.5, 1.0], [x - 0.5, y - 0.5, -1.0]);
+            let hit = bvh.cast(&geometry.positions, &geometry.triangles, &r);
+            let brute = (geometry.triangles.iter().enumerate())
+                .filter_map(|(t, v)| Some((t, r.intersect(v.map(|v| &geometry.positions[v as usize]))?.1)))
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            assert_eq!(hit.map(|h| h.2), brute.map(|b| b.1), "{} {}", x, y);
+        }
+    }
+
+    #[test]
+    fn picks_skinned_vertices_where_they_are_drawn() {
+        // one quad skinned to a joint which moves it up by 1.
+        let positions = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0];
+        let indices = [0.0, 1.0, 2.0, 1.0, 3.0, 2.0];
+        let weights = [[1.0, 0.0, 0.0, 0.0]; 4];
+        let geometry = Geometry::new(
+            &positions,
+            Some(&indices),
+            None,
+            &[0.0; 16],
+            weights.as_flattened(),
+            &[],
+        );
+        let nodes = vec![
+            scene::Node {
+                element: scene::Element::Mesh(0),
+                skin: Some(0),
+                ..Default::default()
+            },
+            scene::Node {
+                translation: Vector3::y(),
+                ..Default::default()
+            },
+        ];
+        let glb = scene::Glb {
+            nodes: nodes,
+            roots: vec![0, 1],
+            meshes: vec![scene::Mesh {
+                primitives: vec![scene::Primitive {
+                    attributes: scene::Attributes {
+                        position: None,
+                        normal: None,
+                        texcoord_0: None,
+                        texcoord_1: None,
+                        joints_0: None,
+                        weights_0: None,
+                    },
+                    targets: Vec::new(),
+                    indices: None,
+                    material: Some(0),
+                    head_less_indices: None,
+                }],
+                weights: None,
+            }],
+            skins: vec![scene::Skin {
+                joints: vec![1],
+                inverse_bind_matrices: vec![Matrix4::identity()],
+            }],
+            geometry: vec![vec![Some(geometry)]],
+            ..Default::default()
+        };
+        let placement = Matrix4::new_translation(&Vector3::new(10.0, 0.0, 0.0));
+        assert!(cast(&glb, &placement, &ray([10.9, 0.5, 1.0], [0.0, 0.0, -1.0]), false).is_none());
+        let hit = cast(&glb, &placement, &ray([10.9, 1.5, 1.0], [0.0, 0.0, -1.0]), false).unwrap();
+        assert_eq!((hit.node, hit.primitive, hit.triangle), (0, 0, 1));
+        assert_eq!(hit.vertex(), 1);
+        assert_eq!(hit.distance, 1.0);
+    }
+}
@@ -0,0 +1,258 @@
+use crate::*;
+use nalgebra::{UnitQuaternion, Vector3};
+
+// the VRM 1.0 humanoid bones, parents before their children.
+pub const BONES: [&str; 55] = [
+    "hips",
+    "spine",
+    "chest",
+    "upperChest",
+    "neck",
+    "head",
+    "leftEye",
+    "rightEye",
+    "jaw",
+    "leftUpperLeg",
+    "leftLowerLeg",
+    "leftFoot",
+    "leftToes",
+    "rightUpperLeg",
+    "rightLowerLeg",
+    "rightFoot",
+    "rightToes",
+    "leftShoulder",
+    "leftUpperArm",
+    "leftLowerArm",
+    "leftHand",
+    "rightShoulder",
+    "rightUpperArm",
+    "rightLowerArm",
+    "rightHand",
+    "leftThumbMetacarpal",
+    "leftThumbProximal",
+    "leftThumbDistal",
+    "leftIndexProximal",
+    "leftIndexIntermediate",
+    "leftIndexDistal",
+    "leftMiddleProximal",
+    "leftMiddleIntermediate",
+    "leftMiddleDistal",
+    "leftRingProximal",
+    "leftRingIntermediate",
+    "leftRingDistal",
+    "leftLittleProximal",
+    "leftLittleIntermediate",
+    "leftLittleDistal",
+    "rightThumbMetacarpal",
+    "rightThumbProximal",
+    "rightThumbDistal",
+    "rightIndexProximal",
+    "rightIndexIntermediate",
+    "rightIndexDistal",
+    "rightMiddleProximal",
+    "rightMiddleIntermediate",
+    "rightMiddleDistal",
+    "rightRingProximal",
+    "rightRingIntermediate",
+    "rightRingDistal",
+    "rightLittleProximal",
+    "rightLittleIntermediate",
+    "rightLittleDistal",
+];
+
+pub const PRESETS: [&str; 3] = ["t-pose", "a-pose", "idle"];
+
+// local rotations of the humanoid bones in the normalized rig of VRM Animation: every bone starts out with the
+// orientation of the model, which faces +z with its left on +x, so that a pose fits any model in a T-pose. the bones
+// which are not listed stay in the rest pose.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HumanoidPose {
+    pub rotations: collections::BTreeMap<String, UnitQuaternion<f32>>,
+}
+
+fn degrees(axis: Vector3<f32>, angle: f32) -> UnitQuaternion<f32> {
+    UnitQuaternion::from_axis_angle(&nalgebra::Unit::new_normalize(axis), angle.to_radians())
+}
+
+// turns +z to where the model faces.
+fn facing(glb: &scene::Glb) -> UnitQuaternion<f32> {
+    glb.look_at.as_ref().map_or_else(UnitQuaternion::identity, |l| l.facing)
+}
+
+impl HumanoidPose {
+    pub fn preset(name: &str) -> Option<Self> {
+        let mut pose = HumanoidPose::default();
+        match name {
+            "t-pose" => (),
+            "a-pose" => {
+                pose.rotate("leftUpperArm", Vector3::z(), -40.0);
+                pose.rotate("rightUpperArm", Vector3::z(), 40.0);
+            }
+            "idle" => {
+                pose.rotate("leftUpperArm", Vector3::z(), -70.0);
+                pose.rotate("rightUpperArm", Vector3::z(), 70.0);
+                pose.rotate("leftLowerArm", Vector3::y(), -20.0);
+                pose.rotate("rightLowerArm", Vector3::y(), 20.0);
+                pose.rotate("leftUpperLeg", Vector3::z(), 3.0);
+                pose.rotate("rightUpperLeg", Vector3::z(), -3.0);
+                pose.rotate("spine", Vector3::x(), -3.0);
+                pose.rotate("head", Vector3::x(), 5.0);
+            }
+            _ => return None,
+        }
+        Some(pose)
+    }
+
+    pub fn rotation(&self, bone: &str) -> UnitQuaternion<f32> {
+        self.rotations
+            .get(bone)
+            .copied()
+            .unwrap_or_else(UnitQuaternion::identity)
+    }
+
+    pub fn set(&mut self, bone: &str, rotation: UnitQuaternion<f32>) {
+        self.rotations.insert(bone.to_string(), rotation);
+    }
+
+    // turns a bone further by "angle" degrees about an axis of its parent.
+    pub fn rotate(&mut self, bone: &str, axis: Vector3<f32>, angle: f32) {
+        self.set(bone, degrees(axis, angle) * self.rotation(bone));
+    }
+
+    // the rotations of the parents of the bones in the rest pose, in the space of the model.
+    fn parent_rotations(glb: &scene::Glb, rest: &animation::Pose) -> Vec<UnitQuaternion<f32>> {
+        let worlds = rest.world_transforms(glb);
+        let mut dst = vec![facing(glb).inverse(); glb.nodes.len()];
+        for (i, node) in glb.nodes.iter().enumerate() {
+            for c in node.children.iter() {
+                dst[*c] = facing(glb).inverse() * utils::rotation(&worlds[i]);
+            }
+        }
+        dst
+    }
+
+    // poses the humanoid bones of a model whose rest pose is "rest".
+    pub fn apply(&self, glb: &mut scene::Glb, rest: &animation::Pose) {
+        let parents = Self::parent_rotations(glb, rest);
+        for (bone, n) in glb.humanoid.iter() {
+            let p = parents[*n];
+            glb.nodes[*n].rotation = p.inverse() * self.rotation(bone) * p * rest.rotations[*n];
+        }
+    }
+
+    // the pose a model is in, the inverse of apply().
+    pub fn capture(glb: &scene::Glb, rest: &animation::Pose) -> Self {
+        let parents = Self::parent_rotations(glb, rest);
+        let mut pose = HumanoidPose::default();
+        for (bone, n) in glb.humanoid.iter() {
+            let p = parents[*n];
+            let q = p * glb.nodes[*n].rotation * rest.rotations[*n].inverse() * p.inverse();
+            if q.angle() > 1e-6 {
+                pose.set(bone, q);
+            }
+        }
+        pose
+    }
+
+    // {"humanBones": {"leftUpperArm": {"rotation": [x, y, z, w]}, ...}}, as the humanoid of VRM Animation.
+    pub fn to_json(&self) -> String {
+        let bones = self
+            .rotations
+            .iter()
+            .map(|(bone, q)| {
+                let [x, y, z, w] = [q.i, q.j, q.k, q.w];
+                format!("    \"{}\": {{\"rotation\": [{}, {}, {}, {}]}}", bone, x, y, z, w)
+            })
+            .collect::<Vec<_>>();
+        format!("{{\n  \"humanBones\": {{\n{}\n  }}\n}}\n", bones.join(",\n"))
+    }
+
+    pub fn from_json(text: &str) -> Option<Self> {
+        let json = tinyjson::JsonParser::new(text.chars()).parse().ok()?;
+        let json: &collections::HashMap<_, _> = json.get()?;
+        let mut pose = HumanoidPose::default();
+        for (bone, json_bone) in json.get("humanBones")?.get::<collections::HashMap<_, _>>()? {
+            let json_bone: &collections::HashMap<_, _> = json_bone.get()?;
+            let rotation = json_bone.get("rotation")?.get::<Vec<_>>()?;
+            let v = rotation
+                .iter()
+                .map(|e| Some(*e.get::<f64>()? as f32))
+                .collect::<Option<Vec<_>>>()?;
+            let [x, y, z, w] = v[..] else {
+                return None;
+            };
+            let q = nalgebra::Quaternion::new(w, x, y, z);
+            if !BONES.contains(&bone.as_str()) || q.norm() == 0.0 {
+                return None;
+            }
+            pose.set(bone, UnitQuaternion::from_quaternion(q));
+        }
+        Some(pose)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Matrix4;
+
+    // a shoulder turned by 90 degrees about y in the rest pose, with the arm along its +z, so that the arm points
+    // to +x like in a T-pose.
+    fn arm() -> (scene::Glb, animation::Pose) {
+        let nodes = vec![
+            scene::Node {
+                rotation: degrees(Vector3::y(), 90.0),
+                children: vec![1],
+                ..Default::default()
+            },
+            scene::Node {
+                translation: Vector3::new(0.0, 0.0, 0.1),
+                children: vec![2],
+                ..Default::default()
+            },
+            scene::Node {
+                translation: Vector3::new(0.0, 0.0, 0.3),
+                ..Default::default()
+            },
+        ];
+        let humanoid = [("leftShoulder", 0), ("leftUpperArm", 1), ("leftLowerArm", 2)];
+        let glb = scene::Glb {
+            nodes: nodes,
+            roots: vec![0],
+            humanoid: humanoid.into_iter().map(|(k, v)| (k.to_string(), v)).collect(),
+            ..Default::default()
+        };
+        let rest = animation::Pose::new(&glb);
+        (glb, rest)
+    }
+
+    fn position(m: &Matrix4<f32>) -> Vector3<f32> {
+        m.column(3).xyz()
+    }
+
+    #[test]
+    fn rotates_in_the_space_of_the_model() {
+        let (mut glb, rest) = arm();
+        HumanoidPose::preset("a-pose").unwrap().apply(&mut glb, &rest);
+        let worlds = glb.world_transforms();
+        let dir = (position(&worlds[2]) - position(&worlds[1])).normalize();
+        let expected = degrees(Vector3::z(), -40.0) * Vector3::x();
+        assert!((dir - expected).norm() < 1e-5, "{}", dir);
+
+        let pose = HumanoidPose::capture(&glb, &rest);
+        assert_eq!(pose.rotations.keys().collect::<Vec<_>>(), ["leftUpperArm"]);
+        let angle = pose.rotation("leftUpperArm").angle_to(&degrees(Vector3::z(), -40.0));
+        assert!(angle < 1e-5, "{}", angle);
+    }
+
+    #[test]
+    fn reads_what_it_writes() {
+        let pose = HumanoidPose::preset("idle").unwrap();
+        let read = HumanoidPose::from_json(&pose.to_json()).unwrap();
+        for (bone, q) in pose.rotations.iter() {
+            assert!(read.rotation(bone).angle_to(q) < 1e-5, "{}", bone);
+        }
+        assert_eq!(read.rotations.len(), pose.rotations.len());
+        assert!(HumanoidPose::from_json("{\"humanBones\": {\"tail\": {\"rotation\": [0, 0, 0, 1]}}}").is_none());
+    }
+}
@@ -0,0 +1,263 @@
+use crate::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMapping {
+    Aces,
+    Agx,
+    Reinhard,
+    Neutral,
+}
+
+#[repr(C, align(16))]
+struct PostUniform {
+    exposure: f32,
+    tone_mapping: u32,
+    bloom_intensity: f32,
+    bloom_threshold: f32,
+}
+
+struct Targets {
+    bloom_views: [wgpu::TextureView; 2],
+    bright_group: wgpu::BindGroup,
+    blur_groups: [wgpu::BindGroup; 2],
+    tone_map_group: wgpu::BindGroup,
+}
+
+pub struct Post {
+    pub tone_mapping: ToneMapping,
+    pub exposure: f32,
+    pub bloom: bool,
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    uniform: wgpu::Buffer,
+    tone_map_pipeline: wgpu::RenderPipeline,
+    bright_pipeline: wgpu::RenderPipeline,
+    blur_pipelines: [wgpu::RenderPipeline; 2],
+    targets: Option<Targets>,
+}
+
+impl ToneMapping {
+    pub const ALL: [Self; 4] = [Self::Aces, Self::Agx, Self::Reinhard, Self::Neutral];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "aces" => Some(Self::Aces),
+            "agx" => Some(Self::Agx),
+            "reinhard" => Some(Self::Reinhard),
+            "neutral" => Some(Self::Neutral),
+            _ => None,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+}
+
+impl Post {
+    pub const BLOOM_INTENSITY: f32 = 0.25;
+    pub const BLOOM_THRESHOLD: f32 = 1.0;
+
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding: binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                texture_entry(3),
+            ],
+            label: None,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let uniform = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: mem::size_of::<PostUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[Some(&layout)],
+            immediate_size: 0,
+        });
+        let shader = device.create_shader_module(wgpu::include_wgsl!("post.wgsl"));
+        let create_pipeline = |fs: &str, format| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: None,
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    compilation_options: Default::default(),
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(fs),
+                    compilation_options: Default::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: Default::default(),
+                depth_stencil: None,
+                multisample: Default::default(),
+                multiview_mask: None,
+                cache: None,
+            })
+        };
+
+        Post {
+            tone_mapping: ToneMapping::Aces,
+            exposure: 0.0,
+            bloom: false,
+            tone_map_pipeline: create_pipeline("fs_tone_map", format),
+            bright_pipeline: create_pipeline("fs_bright", renderer::Renderer::HDR_FORMAT),
+            blur_pipelines: [
+                create_pipeline("fs_blur_h", renderer::Renderer::HDR_FORMAT),
+                create_pipeline("fs_blur_v", renderer::Renderer::HDR_FORMAT),
+            ],
+            layout: layout,
+            sampler: sampler,
+            uniform: uniform,
+            targets: None,
+        }
+    }
+
+    fn create_group(
+        &self,
+        device: &wgpu::Device,
+        src: &wgpu::TextureView,
+        bloom: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.uniform.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(src),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(bloom),
+                },
+            ],
+            label: None,
+        })
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, hdr_view: &wgpu::TextureView, w: u32, h: u32) {
+        let create_view = || {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width: (w / 2).max(1),
+                    height: (h / 2).max(1),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: renderer::Renderer::HDR_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            });
+            texture.create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let bloom_views = [create_view(), create_view()];
+
+        self.targets = Some(Targets {
+            bright_group: self.create_group(device, hdr_view, hdr_view),
+            blur_groups: [
+                self.create_group(device, &bloom_views[0], hdr_view),
+                self.create_group(device, &bloom_views[1], hdr_view),
+            ],
+            tone_map_group: self.create_group(device, hdr_view, &bloom_views[0]),
+            bloom_views: bloom_views,
+        });
+    }
+
+    fn draw(
+        encoder: &mut wgpu::CommandEncoder,
+        pipeline: &wgpu::RenderPipeline,
+        group: &wgpu::BindGroup,
+        view: &wgpu::TextureView,
+    ) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: view,
+                depth_slice: None,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue, view: &wgpu::TextureView) {
+        let targets = self.targets.as_ref().unwrap();
+
+        let uniform = PostUniform {
+            exposure: f32::exp2(self.exposure),
+            tone_mapping: self.tone_mapping as u32,
+            bloom_intensity: if self.bloom { Self::BLOOM_INTENSITY } else { 0.0 },
+            bloom_threshold: Self::BLOOM_THRESHOLD,
+        };
+        queue.write_buffer(&self.uniform, 0, unsafe { utils::as_bytes(&uniform) });
+
+        if self.bloom {
+            let [bloom_0, bloom_1] = &targets.bloom_views;
+            Self::draw(encoder, &self.bright_pipeline, &targets.bright_group, bloom_0);
+            Self::draw(encoder, &self.blur_pipelines[0], &targets.blur_groups[0], bloom_1);
+            Self::draw(encoder, &self.blur_pipelines[1], &targets.blur_groups[1], bloom_0);
+        }
+        Self::draw(encoder, &self.tone_map_pipeline, &targets.tone_map_group, view);
+    }
+}
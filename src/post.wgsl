@@ -0,0 +1,127 @@
+struct Post {
+	exposure: f32,
+	tone_mapping: u32,
+	bloom_intensity: f32,
+	bloom_threshold: f32,
+}
+
+struct VertexToFragment {
+	@builtin(position) builtin_position: vec4<f32>,
+	@location(0) texcoord: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> post: Post;
+@group(0) @binding(1) var src_texture: texture_2d<f32>;
+@group(0) @binding(2) var src_sampler: sampler;
+@group(0) @binding(3) var bloom_texture: texture_2d<f32>;
+
+@vertex fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexToFragment {
+	let position = vec2(f32(vertex_index & 1) * 4.0 - 1.0, f32(vertex_index >> 1) * 4.0 - 1.0);
+	var vtf: VertexToFragment;
+	vtf.builtin_position = vec4(position, 0.0, 1.0);
+	vtf.texcoord = vec2(position.x * 0.5 + 0.5, 0.5 - position.y * 0.5);
+	return vtf;
+}
+
+fn luminance(c: vec3<f32>) -> f32 {
+	return dot(c, vec3(0.2126, 0.7152, 0.0722));
+}
+
+// <https://github.com/TheRealMJP/BakingLab/blob/master/BakingLab/ACES.hlsl>.
+fn tone_map_aces(c: vec3<f32>) -> vec3<f32> {
+	let m_in = mat3x3(
+		vec3(0.59719, 0.07600, 0.02840),
+		vec3(0.35458, 0.90834, 0.13383),
+		vec3(0.04823, 0.01566, 0.83777),
+	);
+	let m_out = mat3x3(
+		vec3(1.60475, -0.10208, -0.00327),
+		vec3(-0.53108, 1.10813, -0.07276),
+		vec3(-0.07367, -0.00605, 1.07602),
+	);
+	let v = m_in * c;
+	let a = v * (v + 0.0245786) - 0.000090537;
+	let b = v * (0.983729 * v + 0.4329510) + 0.238081;
+	return clamp(m_out * (a / b), vec3(0.0), vec3(1.0));
+}
+
+// <https://iolite-engine.com/blog_posts/minimal_agx_implementation>.
+fn tone_map_agx(c: vec3<f32>) -> vec3<f32> {
+	let m_in = mat3x3(
+		vec3(0.842479062253094, 0.0423282422610123, 0.0423756549057051),
+		vec3(0.0784335999999992, 0.878468636469772, 0.0784336),
+		vec3(0.0792237451477643, 0.0791661274605434, 0.879142973793104),
+	);
+	let m_out = mat3x3(
+		vec3(1.19687900512017, -0.0528968517574562, -0.0529716355144438),
+		vec3(-0.0980208811401368, 1.15190312990417, -0.0980434501171241),
+		vec3(-0.0990297440797205, -0.0989611768448433, 1.15107367264116),
+	);
+	let min_ev = -12.47393;
+	let max_ev = 4.026069;
+	let x = (clamp(log2(max(m_in * c, vec3(1e-10))), vec3(min_ev), vec3(max_ev)) - min_ev) / (max_ev - min_ev);
+	let x2 = x * x;
+	let x4 = x2 * x2;
+	let y = 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+	return pow(clamp(m_out * y, vec3(0.0), vec3(1.0)), vec3(2.2));
+}
+
+fn tone_map_reinhard(c: vec3<f32>) -> vec3<f32> {
+	return c / (1.0 + luminance(c));
+}
+
+// <https://github.com/KhronosGroup/ToneMapping/blob/main/PBR_Neutral/README.md>.
+fn tone_map_neutral(c: vec3<f32>) -> vec3<f32> {
+	let start = 0.8 - 0.04;
+	let desaturation = 0.15;
+	let x = min(c.r, min(c.g, c.b));
+	let offset = select(0.04, x - 6.25 * x * x, x < 0.08);
+	let color = c - offset;
+	let peak = max(color.r, max(color.g, color.b));
+	if peak < start {
+		return color;
+	}
+	let d = 1.0 - start;
+	let new_peak = 1.0 - d * d / (peak + d - start);
+	let g = 1.0 - 1.0 / (desaturation * (peak - new_peak) + 1.0);
+	return mix(color * (new_peak / peak), vec3(new_peak), g);
+}
+
+@fragment fn fs_tone_map(vtf: VertexToFragment) -> @location(0) vec4<f32> {
+	let src = textureSampleLevel(src_texture, src_sampler, vtf.texcoord, 0.0);
+	let bloom = textureSampleLevel(bloom_texture, src_sampler, vtf.texcoord, 0.0).rgb;
+	let c = max((src.rgb + post.bloom_intensity * bloom) * post.exposure, vec3(0.0));
+	var dst: vec3<f32>;
+	switch post.tone_mapping {
+		case 0u: { dst = tone_map_aces(c); }
+		case 1u: { dst = tone_map_agx(c); }
+		case 2u: { dst = tone_map_reinhard(c); }
+		default: { dst = tone_map_neutral(c); }
+	}
+	return vec4(dst, src.a);
+}
+
+@fragment fn fs_bright(vtf: VertexToFragment) -> @location(0) vec4<f32> {
+	let c = textureSampleLevel(src_texture, src_sampler, vtf.texcoord, 0.0).rgb;
+	let l = luminance(c);
+	return vec4(c * max(l - post.bloom_threshold, 0.0) / max(l, 1e-4), 1.0);
+}
+
+fn blur(texcoord: vec2<f32>, dir: vec2<f32>) -> vec4<f32> {
+	let weights = array(0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+	let step = dir / vec2<f32>(textureDimensions(src_texture));
+	var c = textureSampleLevel(src_texture, src_sampler, texcoord, 0.0).rgb * weights[0];
+	for (var i = 1; i < 5; i++) {
+		c += textureSampleLevel(src_texture, src_sampler, texcoord + f32(i) * step, 0.0).rgb * weights[i];
+		c += textureSampleLevel(src_texture, src_sampler, texcoord - f32(i) * step, 0.0).rgb * weights[i];
+	}
+	return vec4(c, 1.0);
+}
+
+@fragment fn fs_blur_h(vtf: VertexToFragment) -> @location(0) vec4<f32> {
+	return blur(vtf.texcoord, vec2(1.0, 0.0));
+}
+
+@fragment fn fs_blur_v(vtf: VertexToFragment) -> @location(0) vec4<f32> {
+	return blur(vtf.texcoord, vec2(0.0, 1.0));
+}
@@ -1,57 +1,352 @@
 use crate::*;
-use nalgebra::{Matrix4, Vector4};
+use collections::HashMap;
+use nalgebra::{Matrix4, Point3, Vector3, Vector4};
 
 pub struct Renderer {
     sample_count: u32,
     projection_scale: Vector4<f32>,
+    layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
     pipeline: wgpu::RenderPipeline,
-    color_texture: wgpu::Texture,
-    color_texture_view: wgpu::TextureView,
-    depth_texture: wgpu::Texture,
-    depth_texture_view: wgpu::TextureView,
+    textures: Textures,
+    width: u32,
+    height: u32,
     gpu: gpu_resource::GpuResource,
+    models: HashMap<usize, gpu_resource::GpuModel>,
+    consts: Option<ConstsBuffer>,
+    instances: wgpu::Buffer,
+    animation: AnimationBuffer,
+    pub overlay: overlay::Overlay,
+    pub post: post::Post,
+    outline: outline::Outline,
+    mask_pipeline: wgpu::RenderPipeline,
+    // hides the parts of VRM models which only the others see, such as the head.
+    pub first_person: bool,
+    // the model id and the node which are drawn highlighted.
+    pub selected: Option<(usize, usize)>,
+}
+
+// the constants for adapters without immediates. with or without immediates, the per-draw data comes from the
+// instance vertex buffer, so the constants are the same for all the draws and one uniform is bound once.
+struct ConstsBuffer {
+    layout: wgpu::BindGroupLayout,
+    buffer: wgpu::Buffer,
+    group: wgpu::BindGroup,
+}
+
+// the joint matrices of all the skinned nodes, relative to the nodes, and the morph weights of all the meshes. the
+// instances point at their ranges.
+struct AnimationBuffer {
+    layout: wgpu::BindGroupLayout,
+    joints: wgpu::Buffer,
+    weights: wgpu::Buffer,
+    group: wgpu::BindGroup,
+}
+
+struct Textures {
+    color_texture_view: Option<wgpu::TextureView>,
+    depth_texture_view: wgpu::TextureView,
+    hdr_texture_view: wgpu::TextureView,
+    // the pixels of the selected node, for its outline.
+    mask_texture_view: wgpu::TextureView,
 }
 
 #[repr(C)]
 struct VsConsts {
+    projection_scale: [f32; 4],
+}
+
+#[repr(C)]
+struct Instance {
     m_position: [[f32; 4]; 4],
     m_normal: [[f32; 4]; 3],
-    projection_scale: [f32; 4],
+    joint_offset: u32,
+    // the offset of the weights, the number of targets, the offset of the deltas and the number of vertices.
+    morph: [u32; 4],
+    selected: u32,
+}
+
+struct Draw {
+    model: usize,
+    node: usize,
+    material: usize,
+    mesh: usize,
+    primitive: usize,
+    head_less: bool,
+    instance: Instance,
+}
+
+// where the animation data of the nodes, meshes and primitives of a model starts.
+struct Offsets<'a> {
+    joints: Vec<u32>,
+    weights: Vec<u32>,
+    deltas: &'a [Vec<u32>],
+}
+
+struct Batch {
+    model: usize,
+    material: usize,
+    mesh: usize,
+    primitive: usize,
+    head_less: bool,
+    selected: bool,
+    instances: ops::Range<u32>,
+}
+
+impl ConstsBuffer {
+    fn new(device: &wgpu::Device) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: wgpu::BufferSize::new(mem::size_of::<VsConsts>() as u64),
+                },
+                count: None,
+            }],
+            label: None,
+        });
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: mem::size_of::<VsConsts>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+            label: None,
+        });
+        ConstsBuffer {
+            layout: layout,
+            buffer: buffer,
+            group: group,
+        }
+    }
+}
+
+impl AnimationBuffer {
+    fn new(device: &wgpu::Device) -> Self {
+        let entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding: binding,
+            visibility: wgpu::ShaderStages::VERTEX,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[entry(0), entry(1)],
+            label: None,
+        });
+        let joints = Self::create_buffer(device, mem::size_of::<Matrix4<f32>>());
+        let weights = Self::create_buffer(device, mem::size_of::<f32>());
+        let group = Self::create_group(device, &layout, &joints, &weights);
+        AnimationBuffer {
+            layout: layout,
+            joints: joints,
+            weights: weights,
+            group: group,
+        }
+    }
+
+    fn create_buffer(device: &wgpu::Device, size: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: size.max(16) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        joints: &wgpu::Buffer,
+        weights: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: joints.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: weights.as_entire_binding(),
+                },
+            ],
+            label: None,
+        })
+    }
+
+    fn reserve(&mut self, device: &wgpu::Device, joints: usize, weights: usize) {
+        let joints = joints * mem::size_of::<Matrix4<f32>>();
+        let weights = weights * mem::size_of::<f32>();
+        if joints as u64 <= self.joints.size() && weights as u64 <= self.weights.size() {
+            return;
+        }
+        if joints as u64 > self.joints.size() {
+            self.joints = Self::create_buffer(device, joints);
+        }
+        if weights as u64 > self.weights.size() {
+            self.weights = Self::create_buffer(device, weights);
+        }
+        self.group = Self::create_group(device, &self.layout, &self.joints, &self.weights);
+    }
 }
 
 impl Renderer {
-    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+    pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    pub const IMMEDIATE_SIZE: u32 = mem::size_of::<VsConsts>() as u32;
 
-    pub fn new(device: &wgpu::Device, sample_count: u32) -> Result<Self, Box<dyn error::Error>> {
-        let gpu = gpu_resource::GpuResource::new(&device);
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        immediates: bool,
+    ) -> Result<Self, Box<dyn error::Error>> {
+        let gpu = gpu_resource::GpuResource::new(&device, &queue);
+        let consts = if immediates {
+            None
+        } else {
+            Some(ConstsBuffer::new(device))
+        };
+        let animation = AnimationBuffer::new(device);
+        let overlay = overlay::Overlay::new(device, queue, Self::HDR_FORMAT, format, sample_count);
+        let mut post = post::Post::new(device, format);
+        let mut outline = outline::Outline::new(device, format);
 
-        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        let layout = match &consts {
+            None => device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    Some(&gpu.material_layout),
+                    None,
+                    Some(&animation.layout),
+                    Some(&gpu.morph_layout),
+                ],
+                immediate_size: Self::IMMEDIATE_SIZE,
+            }),
+            Some(consts) => device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    Some(&gpu.material_layout),
+                    Some(&consts.layout),
+                    Some(&animation.layout),
+                    Some(&gpu.morph_layout),
+                ],
+                immediate_size: 0,
+            }),
+        };
+        // a module which declares immediates is rejected without the feature, so "imm" is declared by the variant.
+        let source = match consts {
+            None => concat!(include_str!("shader.wgsl"), "var<immediate> imm: Immediate;\n"),
+            Some(_) => concat!(
+                include_str!("shader.wgsl"),
+                "@group(1) @binding(0) var<uniform> imm: Immediate;\n"
+            ),
+        };
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: None,
-            bind_group_layouts: &[Some(&gpu.material_layout)],
-            immediate_size: mem::size_of::<VsConsts>() as u32,
+            source: wgpu::ShaderSource::Wgsl(source.into()),
         });
-        let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        let pipeline = Self::create_pipeline(device, &layout, &shader, &gpu, sample_count, false);
+        let mask_pipeline = Self::create_pipeline(device, &layout, &shader, &gpu, 1, true);
+
+        let textures = Self::create_textures(device, 1, 1, sample_count);
+        post.resize(device, &textures.hdr_texture_view, 1, 1);
+        outline.resize(device, &textures.mask_texture_view);
+
+        Ok(Renderer {
+            sample_count: sample_count,
+            projection_scale: Vector4::new(1.0, 1.0, 1.0, f32::powi(0.5, 32)),
+            layout: layout,
+            shader: shader,
+            pipeline: pipeline,
+            textures: textures,
+            width: 1,
+            height: 1,
+            gpu: gpu,
+            models: HashMap::new(),
+            consts: consts,
+            instances: Self::create_instances(device, 1),
+            animation: animation,
+            overlay: overlay,
+            post: post,
+            outline: outline,
+            mask_pipeline: mask_pipeline,
+            first_person: false,
+            selected: None,
+        })
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        gpu: &gpu_resource::GpuResource,
+        sample_count: u32,
+        mask: bool,
+    ) -> wgpu::RenderPipeline {
+        // the mask takes the same vertices, without the depth test so that the hidden parts are outlined too.
+        let (fs, format) = match mask {
+            true => ("fs_mask", outline::Outline::MASK_FORMAT),
+            false => ("fs_main", Self::HDR_FORMAT),
+        };
+        let [position, normal, texcoord_0, texcoord_1, skin] = gpu.vertex_layouts();
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: None,
-            layout: Some(&layout),
+            layout: Some(layout),
             vertex: wgpu::VertexState {
-                module: &shader,
+                module: shader,
                 entry_point: None,
                 compilation_options: Default::default(),
-                buffers: &gpu.vertex_layouts(),
+                buffers: &[
+                    position,
+                    normal,
+                    texcoord_0,
+                    texcoord_1,
+                    wgpu::VertexBufferLayout {
+                        array_stride: mem::size_of::<Instance>() as u64,
+                        step_mode: wgpu::VertexStepMode::Instance,
+                        attributes: &wgpu::vertex_attr_array![
+                            4 => Float32x4,
+                            5 => Float32x4,
+                            6 => Float32x4,
+                            7 => Float32x4,
+                            8 => Float32x4,
+                            9 => Float32x4,
+                            10 => Float32x4,
+                            11 => Uint32,
+                            14 => Uint32x4,
+                            15 => Uint32,
+                        ],
+                    },
+                    skin,
+                ],
             },
             fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: None,
+                module: shader,
+                entry_point: Some(fs),
                 compilation_options: Default::default(),
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: Self::FORMAT,
+                    format: format,
                     blend: None,
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
             }),
             primitive: Default::default(),
-            depth_stencil: Some(wgpu::DepthStencilState {
+            depth_stencil: (!mask).then(|| wgpu::DepthStencilState {
                 format: wgpu::TextureFormat::Depth32Float,
                 depth_write_enabled: Some(true),
                 depth_compare: Some(wgpu::CompareFunction::Greater),
@@ -61,28 +356,103 @@ impl Renderer {
             multisample: wgpu::MultisampleState {
                 count: sample_count,
                 mask: !0,
-                alpha_to_coverage_enabled: true,
+                alpha_to_coverage_enabled: sample_count > 1,
             },
             multiview_mask: None,
             cache: None,
-        });
+        })
+    }
 
-        let (color_tex, color_view, depth_tex, depth_view) = Self::create_textures(device, 1, 1, sample_count);
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
 
-        Ok(Renderer {
-            sample_count: sample_count,
-            projection_scale: Vector4::new(1.0, 1.0, 1.0, f32::powi(0.5, 32)),
-            pipeline: pipeline,
-            color_texture: color_tex,
-            color_texture_view: color_view,
-            depth_texture: depth_tex,
-            depth_texture_view: depth_view,
-            gpu: gpu,
-        })
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        self.sample_count = sample_count;
+        self.pipeline = Self::create_pipeline(device, &self.layout, &self.shader, &self.gpu, sample_count, false);
+        self.overlay.set_sample_count(device, sample_count);
+        self.textures = Self::create_textures(device, self.width, self.height, sample_count);
+        self.post
+            .resize(device, &self.textures.hdr_texture_view, self.width, self.height);
+        self.outline.resize(device, &self.textures.mask_texture_view);
+    }
+
+    pub fn update_material(&self, queue: &wgpu::Queue, model: &world::Model, index: usize) {
+        if let Some(gpu_model) = self.models.get(&model.id) {
+            self.gpu.update_material(queue, gpu_model, &model.glb, index);
+        }
+    }
+
+    pub fn set_image(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        model: &world::Model,
+        textures: &[usize],
+        image: Option<&scene::Image>,
+    ) -> Result<(), String> {
+        match self.models.get_mut(&model.id) {
+            Some(gpu_model) => self
+                .gpu
+                .set_image(device, queue, gpu_model, &model.glb, textures, image),
+            None => Ok(()),
+        }
+    }
+
+    pub fn has_model(&self, id: usize) -> bool {
+        self.models.contains_key(&id)
+    }
+
+    pub fn update(&mut self, device: &wgpu::Device, world: &world::World) {
+        self.models.retain(|id, _| world.models.iter().any(|m| m.id == *id));
+        for model in world.models.iter() {
+            if let (false, Some(blob)) = (self.models.contains_key(&model.id), &model.glb.blob) {
+                self.models.insert(model.id, self.gpu.upload(device, &model.glb, blob));
+            }
+        }
+
+        let capacity = world
+            .models
+            .iter()
+            .flat_map(|m| m.glb.nodes.iter().map(|n| (&m.glb, n)))
+            .map(|(glb, n)| match n.element {
+                scene::Element::Mesh(mesh) => glb.meshes[mesh].primitives.len(),
+                scene::Element::None => 0,
+            })
+            .sum::<usize>();
+        if (capacity * mem::size_of::<Instance>()) as u64 > self.instances.size() {
+            self.instances = Self::create_instances(device, capacity);
+        }
+
+        let joints = world
+            .models
+            .iter()
+            .flat_map(|m| m.glb.nodes.iter().map(|n| (&m.glb, n)))
+            .filter_map(|(glb, n)| match (&n.element, n.skin) {
+                (scene::Element::Mesh(_), Some(skin)) => Some(glb.skins[skin].joints.len()),
+                _ => None,
+            })
+            .sum::<usize>();
+        let weights = world
+            .models
+            .iter()
+            .flat_map(|m| m.glb.meshes.iter())
+            .map(Self::target_count)
+            .sum::<usize>();
+        self.animation.reserve(device, joints, weights);
     }
 
-    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, glb: &scene::Glb) {
-        self.gpu.update(&device, &queue, &glb);
+    fn target_count(mesh: &scene::Mesh) -> usize {
+        mesh.primitives.iter().map(|p| p.targets.len()).max().unwrap_or(0)
+    }
+
+    fn create_instances(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (capacity.max(1) * mem::size_of::<Instance>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
     }
 
     pub fn resize(&mut self, device: &wgpu::Device, w: u32, h: u32) {
@@ -92,11 +462,11 @@ impl Renderer {
         self.projection_scale[0] = nf / wf;
         self.projection_scale[1] = nf / hf;
 
-        let (color_tex, color_view, depth_tex, depth_view) = Self::create_textures(device, w, h, self.sample_count);
-        self.color_texture = color_tex;
-        self.color_texture_view = color_view;
-        self.depth_texture = depth_tex;
-        self.depth_texture_view = depth_view;
+        self.textures = Self::create_textures(device, w, h, self.sample_count);
+        self.post.resize(device, &self.textures.hdr_texture_view, w, h);
+        self.outline.resize(device, &self.textures.mask_texture_view);
+        self.width = w;
+        self.height = h;
     }
 
     // XXX
@@ -104,25 +474,119 @@ impl Renderer {
         self.projection_scale[2] = s;
     }
 
-    pub fn render<'a>(
-        &'a self,
+    // the ray from the camera through a pixel of the window. the projection maps the depth to w, so the points which
+    // land on the pixel at a depth of 1 give the direction.
+    pub fn ray(&self, camera: &scene::Node, x: f32, y: f32) -> pick::Ray {
+        let s = &self.projection_scale;
+        let ndc_x = 2.0 * x / self.width as f32 - 1.0;
+        let ndc_y = 1.0 - 2.0 * y / self.height as f32;
+        let dir = Vector3::new(ndc_x * s[2] / s[0], ndc_y * s[2] / s[1], 1.0);
+        let transform = camera.transform();
+        pick::Ray {
+            origin: transform.transform_point(&Point3::origin()),
+            dir: transform.transform_vector(&dir),
+        }
+    }
+
+    pub fn render(
+        &self,
         encoder: &mut wgpu::CommandEncoder,
-        glb: &scene::Glb,
+        queue: &wgpu::Queue,
+        world: &world::World,
         view: &wgpu::TextureView,
         camera: &scene::Node,
     ) {
+        self.overlay.update(queue, &camera.transform(), &self.projection_scale);
+
+        let mut draws = Vec::new();
+        let transform = camera.transform().try_inverse().unwrap();
+        let mut joints = Vec::new();
+        let mut weights = Vec::new();
+        for (i, model) in world.models.iter().enumerate() {
+            let Some(gpu_model) = self.models.get(&model.id) else {
+                continue;
+            };
+            let offsets = Offsets {
+                joints: Self::collect_joints(&mut joints, &model.glb),
+                weights: Self::collect_weights(&mut weights, &model.glb),
+                deltas: &gpu_model.morph_offsets,
+            };
+            let transform = transform * model.placement.transform();
+            for n in model.glb.roots.iter() {
+                self.collect_nodes(&mut draws, i, &model.glb, &offsets, *n, &transform);
+            }
+        }
+        if !joints.is_empty() {
+            queue.write_buffer(&self.animation.joints, 0, unsafe { utils::slice_as_bytes(&joints) });
+        }
+        if !weights.is_empty() {
+            queue.write_buffer(&self.animation.weights, 0, unsafe { utils::slice_as_bytes(&weights) });
+        }
+
+        for draw in draws.iter_mut() {
+            let node = (world.models[draw.model].id, draw.node);
+            draw.instance.selected = (self.selected == Some(node)) as u32;
+        }
+
+        // sort by state and merge the same primitives into instanced draws.
+        // the selected instances get batches of their own, which are drawn again into the mask.
+        let key = |d: &Draw| {
+            (
+                d.model,
+                d.material,
+                d.mesh,
+                d.primitive,
+                d.head_less,
+                d.instance.selected != 0,
+            )
+        };
+        draws.sort_by_key(key);
+        let mut batches: Vec<Batch> = Vec::new();
+        for (i, draw) in draws.iter().enumerate() {
+            match batches.last_mut() {
+                Some(b) if (b.model, b.material, b.mesh, b.primitive, b.head_less, b.selected) == key(draw) => {
+                    b.instances.end += 1;
+                }
+                _ => batches.push(Batch {
+                    model: draw.model,
+                    material: draw.material,
+                    mesh: draw.mesh,
+                    primitive: draw.primitive,
+                    head_less: draw.head_less,
+                    selected: draw.instance.selected != 0,
+                    instances: i as u32..i as u32 + 1,
+                }),
+            }
+        }
+        let instances = draws.into_iter().map(|d| d.instance).collect::<Vec<_>>();
+        queue.write_buffer(&self.instances, 0, unsafe { utils::slice_as_bytes(&instances) });
+        let consts = VsConsts {
+            projection_scale: *self.projection_scale.as_ref(),
+        };
+        if let Some(buffer) = &self.consts {
+            queue.write_buffer(&buffer.buffer, 0, unsafe { utils::as_bytes(&consts) });
+        }
+
         let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &self.color_texture_view,
+                view: self
+                    .textures
+                    .color_texture_view
+                    .as_ref()
+                    .unwrap_or(&self.textures.hdr_texture_view),
                 depth_slice: None,
-                resolve_target: Some(view),
+                resolve_target: self
+                    .textures
+                    .color_texture_view
+                    .as_ref()
+                    .map(|_| &self.textures.hdr_texture_view),
                 ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    load: wgpu::LoadOp::Clear(self.overlay.clear_color()),
                     store: wgpu::StoreOp::Store,
                 },
             })],
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: &self.depth_texture_view,
+                view: &self.textures.depth_texture_view,
                 depth_ops: Some(wgpu::Operations {
                     load: wgpu::LoadOp::Clear(0.0),
                     store: wgpu::StoreOp::Store,
@@ -131,73 +595,252 @@ impl Renderer {
             }),
             ..Default::default()
         });
+        self.overlay.draw_background(&mut pass);
+
         pass.set_pipeline(&self.pipeline);
+        self.draw_batches(&mut pass, world, &batches, &consts, false);
 
-        let transform = camera.transform().try_inverse().unwrap();
-        for n in glb.roots.iter() {
-            self.render_nodes(&mut pass, glb, *n, &transform);
+        self.overlay.draw_grid(&mut pass);
+        self.overlay.draw_axes(&mut pass, self.width, self.height);
+        drop(pass);
+
+        let selected = batches.iter().any(|b| b.selected);
+        if selected {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.textures.mask_texture_view,
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                ..Default::default()
+            });
+            pass.set_pipeline(&self.mask_pipeline);
+            self.draw_batches(&mut pass, world, &batches, &consts, true);
+        }
+
+        self.post.render(encoder, queue, view);
+        if selected {
+            self.outline.draw(encoder, view);
         }
+        self.overlay.draw_text(encoder, view);
     }
 
-    pub fn render_nodes<'a>(
+    // draws the batches, or only the selected ones, with the pipeline which is set.
+    fn draw_batches<'a>(
         &'a self,
         pass: &mut wgpu::RenderPass<'a>,
+        world: &world::World,
+        batches: &[Batch],
+        consts: &VsConsts,
+        selected_only: bool,
+    ) {
+        pass.set_vertex_buffer(4, self.instances.slice(..));
+        pass.set_bind_group(2, &self.animation.group, &[]);
+        match &self.consts {
+            None => unsafe { pass.set_immediates(0, utils::as_bytes(consts)) },
+            Some(buffer) => pass.set_bind_group(1, &buffer.group, &[]),
+        }
+        let mut material = None;
+        for batch in batches {
+            if selected_only && !batch.selected {
+                continue;
+            }
+            let model = &world.models[batch.model];
+            let Some(gpu_model) = self.models.get(&model.id) else {
+                continue;
+            };
+            if material.map(|(model, _)| model) != Some(batch.model) {
+                pass.set_bind_group(3, &gpu_model.morph_group, &[]);
+            }
+            if material != Some((batch.model, batch.material)) {
+                pass.set_bind_group(0, &gpu_model.materials[batch.material].0, &[]);
+                material = Some((batch.model, batch.material));
+            }
+            gpu_model.draw_primitive(
+                pass,
+                &model.glb,
+                batch.mesh,
+                batch.primitive,
+                batch.head_less,
+                batch.instances.clone(),
+            );
+        }
+    }
+
+    // appends the joint matrices of the skinned nodes and returns their offsets per node. the matrices are relative
+    // to the skinned node, so that its transform can be applied as for the other nodes.
+    fn collect_joints(joints: &mut Vec<Matrix4<f32>>, glb: &scene::Glb) -> Vec<u32> {
+        let mut offsets = vec![0; glb.nodes.len()];
+        if glb.skins.is_empty() {
+            return offsets;
+        }
+        let worlds = glb.world_transforms();
+        for (i, node) in glb.nodes.iter().enumerate() {
+            let (scene::Element::Mesh(_), Some(skin)) = (&node.element, node.skin) else {
+                continue;
+            };
+            let skin = &glb.skins[skin];
+            let inverse = worlds[i].try_inverse().unwrap_or_else(Matrix4::identity);
+            offsets[i] = joints.len() as u32;
+            for (joint, inverse_bind) in skin.joints.iter().zip(skin.inverse_bind_matrices.iter()) {
+                joints.push(inverse * worlds[*joint] * inverse_bind);
+            }
+        }
+        offsets
+    }
+
+    // appends the morph weights and returns their offsets per mesh.
+    fn collect_weights(weights: &mut Vec<f32>, glb: &scene::Glb) -> Vec<u32> {
+        glb.meshes
+            .iter()
+            .map(|mesh| {
+                let offset = weights.len() as u32;
+                let count = Self::target_count(mesh);
+                let mesh_weights = mesh.weights.as_deref().unwrap_or(&[]);
+                weights.extend((0..count).map(|i| mesh_weights.get(i).copied().unwrap_or(0.0)));
+                offset
+            })
+            .collect()
+    }
+
+    fn projection(&self) -> Matrix4<f32> {
+        let s = &self.projection_scale;
+        #[rustfmt::skip]
+        let projection = Matrix4::new(
+            s[0], 0.0, 0.0, 0.0,
+            0.0, s[1], 0.0, 0.0,
+            0.0, 0.0, 0.0, s[3],
+            0.0, 0.0, s[2], 0.0,
+        );
+        projection
+    }
+
+    fn collect_nodes(
+        &self,
+        draws: &mut Vec<Draw>,
+        model: usize,
         glb: &scene::Glb,
+        offsets: &Offsets,
         root: usize,
         transform: &Matrix4<f32>,
     ) {
         let root_node = &glb.nodes[root];
         let transform = transform * root_node.transform();
-        if let scene::Element::Mesh(mesh) = root_node.element {
-            let buf = VsConsts {
-                m_position: *transform.as_ref(),
-                m_normal: *transform.fixed_columns::<3>(0).as_ref(), // XXX
-                projection_scale: *self.projection_scale.as_ref(),
-            };
-            unsafe { pass.set_immediates(0, utils::as_bytes(&buf)) }
-            self.gpu.draw_mesh(pass, glb, mesh, 0);
+        let head_less = match (self.first_person, root_node.first_person) {
+            (true, scene::FirstPerson::ThirdPersonOnly) | (false, scene::FirstPerson::FirstPersonOnly) => None,
+            (true, scene::FirstPerson::Auto) => Some(true),
+            _ => Some(false),
+        };
+        if let (scene::Element::Mesh(mesh), Some(head_less)) = (&root_node.element, head_less) {
+            let mesh = *mesh;
+            let frustum = frustum::Frustum::new(&(self.projection() * transform));
+            for (i, primitive) in glb.meshes[mesh].primitives.iter().enumerate() {
+                let Some(material) = primitive.material else { continue };
+                // the bounds do not hold for the skinned and morphed vertices.
+                let animated = root_node.skin.is_some() || !primitive.targets.is_empty();
+                if let (false, Some(aabb)) = (animated, glb.primitive_bounds(primitive)) {
+                    if !frustum.intersects(&aabb) {
+                        continue;
+                    }
+                }
+                draws.push(Draw {
+                    model: model,
+                    node: root,
+                    material: material,
+                    mesh: mesh,
+                    primitive: i,
+                    head_less: head_less,
+                    instance: Instance {
+                        m_position: *transform.as_ref(),
+                        m_normal: *transform.fixed_columns::<3>(0).as_ref(), // XXX
+                        joint_offset: offsets.joints[root],
+                        morph: [
+                            offsets.weights[mesh],
+                            primitive.targets.len() as u32,
+                            offsets.deltas[mesh][i],
+                            primitive
+                                .attributes
+                                .position
+                                .map_or(0, |p| glb.accessors[p].count as u32),
+                        ],
+                        selected: 0,
+                    },
+                });
+            }
         }
         for n in root_node.children.iter() {
-            self.render_nodes(pass, glb, *n, &transform);
+            self.collect_nodes(draws, model, glb, offsets, *n, &transform);
         }
     }
 
-    fn create_textures(
-        device: &wgpu::Device,
-        w: u32,
-        h: u32,
-        sample_count: u32,
-    ) -> (wgpu::Texture, wgpu::TextureView, wgpu::Texture, wgpu::TextureView) {
+    fn create_textures(device: &wgpu::Device, w: u32, h: u32, sample_count: u32) -> Textures {
         let size = wgpu::Extent3d {
             width: w,
             height: h,
             depth_or_array_layers: 1,
         };
 
-        let color_tex = device.create_texture(&wgpu::TextureDescriptor {
+        let color_view = if sample_count > 1 {
+            let color_tex = device.create_texture(&wgpu::TextureDescriptor {
+                label: None,
+                size: size,
+                mip_level_count: 1,
+                sample_count: sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format: Self::HDR_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            });
+            Some(color_tex.create_view(&wgpu::TextureViewDescriptor::default()))
+        } else {
+            None
+        };
+
+        let depth_tex = device.create_texture(&wgpu::TextureDescriptor {
             label: None,
             size: size,
             mip_level_count: 1,
             sample_count: sample_count,
             dimension: wgpu::TextureDimension::D2,
-            format: Self::FORMAT,
+            format: wgpu::TextureFormat::Depth32Float,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             view_formats: &[],
         });
-        let color_view = color_tex.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth_view = depth_tex.create_view(&wgpu::TextureViewDescriptor::default());
 
-        let depth_tex = device.create_texture(&wgpu::TextureDescriptor {
+        let hdr_tex = device.create_texture(&wgpu::TextureDescriptor {
             label: None,
             size: size,
             mip_level_count: 1,
-            sample_count: sample_count,
+            sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth32Float,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: Self::HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
             view_formats: &[],
         });
-        let depth_view = depth_tex.create_view(&wgpu::TextureViewDescriptor::default());
+        let hdr_view = hdr_tex.create_view(&wgpu::TextureViewDescriptor::default());
 
-        (color_tex, color_view, depth_tex, depth_view)
+        let mask_tex = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: outline::Outline::MASK_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let mask_view = mask_tex.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Textures {
+            color_texture_view: color_view,
+            depth_texture_view: depth_view,
+            hdr_texture_view: hdr_view,
+            mask_texture_view: mask_view,
+        }
     }
 }
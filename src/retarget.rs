@@ -0,0 +1,414 @@
+use crate::*;
+use nalgebra::{Matrix3, Matrix4, Point3, UnitQuaternion, Vector3};
+
+// the names of the bones on the center line, as found in common skeletons after normalizing.
+const CENTER: [(&str, &[&str]); 6] = [
+    ("hips", &["hips", "hip", "pelvis"]),
+    ("spine", &["spine", "abdomen", "spine01"]),
+    ("chest", &["chest", "spine1", "spine02"]),
+    ("upperChest", &["upperchest", "spine2", "spine03", "chest2"]),
+    ("neck", &["neck", "neck1", "neck01"]),
+    ("head", &["head"]),
+];
+
+// the bones on both sides, named with "left" or "l" before or after them.
+const LIMBS: [(&str, &[&str]); 9] = [
+    ("Shoulder", &["shoulder", "collar", "clavicle"]),
+    ("UpperArm", &["upperarm", "arm", "shldr", "uparm"]),
+    ("LowerArm", &["lowerarm", "forearm"]),
+    ("Hand", &["hand"]),
+    ("UpperLeg", &["upperleg", "upleg", "thigh"]),
+    ("LowerLeg", &["lowerleg", "leg", "shin", "calf"]),
+    ("Foot", &["foot"]),
+    ("Toes", &["toes", "toebase", "toe"]),
+    ("Eye", &["eye"]),
+];
+
+// the fingers are numbered from the hand, as in "LeftHandIndex1".
+const FINGERS: [(&str, &[&str]); 5] = [
+    ("Thumb", &["thumb"]),
+    ("Index", &["index"]),
+    ("Middle", &["middle"]),
+    ("Ring", &["ring"]),
+    ("Little", &["little", "pinky"]),
+];
+
+const SIDES: [(&str, &[&str]); 2] = [("left", &["left", "l"]), ("right", &["right", "r"])];
+
+// the prefixes of rigging tools.
+const PREFIXES: [&str; 5] = ["mixamorig", "bip001", "bip01", "jbipc", "jbip"];
+
+// the bones which give the direction of a bone, tried in order.
+fn tails(bone: &str) -> Vec<String> {
+    let center = ["hips", "spine", "chest", "upperChest", "neck", "head"];
+    if let Some(i) = center.iter().position(|b| *b == bone) {
+        return center[i + 1..].iter().map(|b| b.to_string()).collect();
+    }
+    let Some((side, rest)) = SIDES
+        .iter()
+        .find_map(|(side, _)| Some((*side, bone.strip_prefix(side)?)))
+    else {
+        return Vec::new();
+    };
+    let next = match rest {
+        "Shoulder" => vec!["UpperArm"],
+        "UpperArm" => vec!["LowerArm"],
+        "LowerArm" => vec!["Hand"],
+        "Hand" => vec!["MiddleProximal", "IndexProximal"],
+        "UpperLeg" => vec!["LowerLeg"],
+        "LowerLeg" => vec!["Foot"],
+        "Foot" => vec!["Toes"],
+        "ThumbMetacarpal" => vec!["ThumbProximal"],
+        "ThumbProximal" => vec!["ThumbDistal"],
+        _ => match (rest.strip_suffix("Proximal"), rest.strip_suffix("Intermediate")) {
+            (Some(finger), _) => return vec![format!("{}{}Intermediate", side, finger)],
+            (_, Some(finger)) => return vec![format!("{}{}Distal", side, finger)],
+            _ => Vec::new(),
+        },
+    };
+    next.into_iter().map(|b| format!("{}{}", side, b)).collect()
+}
+
+// the VRM humanoid bones with the normalized names they go by, best first.
+fn aliases() -> Vec<(String, Vec<String>)> {
+    let mut dst: Vec<(String, Vec<String>)> = Vec::new();
+    for (bone, names) in CENTER {
+        dst.push((bone.to_string(), names.iter().map(|n| n.to_string()).collect()));
+    }
+    for (side, sides) in SIDES {
+        let name = |base: &str| {
+            let before = sides.iter().map(|s| format!("{}{}", s, base));
+            before
+                .chain(sides.iter().map(|s| format!("{}{}", base, s)))
+                .collect::<Vec<_>>()
+        };
+        for (limb, names) in LIMBS {
+            dst.push((
+                format!("{}{}", side, limb),
+                names.iter().flat_map(|n| name(n)).collect(),
+            ));
+        }
+        for (finger, names) in FINGERS {
+            let segments = match finger {
+                "Thumb" => ["Metacarpal", "Proximal", "Distal"],
+                _ => ["Proximal", "Intermediate", "Distal"],
+            };
+            for (i, segment) in segments.iter().enumerate() {
+                let numbered = names.iter().flat_map(|n| {
+                    let n = [
+                        format!("hand{}{}", n, i + 1),
+                        format!("{}{}", n, i + 1),
+                        format!("{}0{}", n, i + 1),
+                    ];
+                    n.into_iter().flat_map(|n| name(&n))
+                });
+                dst.push((format!("{}{}{}", side, finger, segment), numbered.collect()));
+            }
+        }
+    }
+    // the VRM names themselves come first.
+    for (bone, names) in dst.iter_mut() {
+        names.insert(0, normalize(bone));
+    }
+    dst
+}
+
+// lower case without the namespace, the prefix of the tool and the separators.
+fn normalize(name: &str) -> String {
+    let name = name.rsplit(':').next().unwrap_or(name);
+    let name = name
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect::<String>();
+    match PREFIXES.iter().find_map(|p| name.strip_prefix(p)) {
+        Some(rest) if !rest.is_empty() => rest.to_string(),
+        _ => name,
+    }
+}
+
+// the humanoid bones of a scene, from the VRM extension or guessed from the names of the nodes.
+pub fn humanoid(glb: &scene::Glb) -> collections::HashMap<String, usize> {
+    if !glb.humanoid.is_empty() {
+        return glb.humanoid.clone();
+    }
+    let mut names = collections::HashMap::new();
+    for (i, node) in glb.nodes.iter().enumerate() {
+        names.entry(normalize(&node.name)).or_insert(i);
+    }
+    let mut dst = collections::HashMap::new();
+    let mut used = collections::HashSet::new();
+    for (bone, aliases) in aliases() {
+        if let Some(n) = aliases.iter().filter_map(|a| names.get(a)).find(|n| !used.contains(*n)) {
+            used.insert(*n);
+            dst.insert(bone, *n);
+        }
+    }
+    dst
+}
+
+fn position(m: &Matrix4<f32>) -> Point3<f32> {
+    m.transform_point(&Point3::origin())
+}
+
+// the height of the hips above the lowest node, which is about the length of the legs.
+fn height(worlds: &[Matrix4<f32>], hips: usize, up: &Vector3<f32>) -> f32 {
+    let lowest = worlds
+        .iter()
+        .map(|m| position(m).coords.dot(up))
+        .fold(f32::INFINITY, f32::min);
+    position(&worlds[hips]).coords.dot(up) - lowest
+}
+
+// the orientation of a skeleton: x to its left, y up and z forward.
+fn frame(bones: &collections::HashMap<String, usize>, worlds: &[Matrix4<f32>]) -> Option<UnitQuaternion<f32>> {
+    let at = |bone: &str| Some(position(&worlds[*bones.get(bone)?]));
+    let top = ["head", "neck", "upperChest", "chest", "spine"]
+        .iter()
+        .find_map(|b| at(b))?;
+    let up = (top - at("hips")?).try_normalize(0.0)?;
+    let left = match (at("leftUpperLeg"), at("rightUpperLeg")) {
+        (Some(l), Some(r)) => l - r,
+        _ => at("leftUpperArm")? - at("rightUpperArm")?,
+    };
+    let left = (left - up * left.dot(&up)).try_normalize(0.0)?;
+    let m = Matrix3::from_columns(&[left, up, left.cross(&up)]);
+    Some(UnitQuaternion::from_matrix(&m))
+}
+
+#[derive(Clone)]
+struct Bone {
+    source: usize,
+    // the world rotation of the target is facing * source * offset.
+    offset: UnitQuaternion<f32>,
+}
+
+// maps a clip of another skeleton onto the humanoid bones of "target", whose rest pose is "rest". the source is
+// expected in its rest pose. the bones are turned so that they point where the source bones point, which makes up
+// for different rest poses, and the motion of the hips is scaled by the height of the hips. the tracks of VRM
+// Animation which are not bound to nodes are taken as they are.
+pub fn retarget(
+    source: &scene::Glb,
+    clip: &animation::Clip,
+    target: &scene::Glb,
+    rest: &animation::Pose,
+) -> Option<animation::Clip> {
+    let source_bones = humanoid(source);
+    let target_bones = humanoid(target);
+    let source_worlds = source.world_transforms();
+    let target_worlds = rest.world_transforms(target);
+    let source_frame = frame(&source_bones, &source_worlds)?;
+    let target_frame = frame(&target_bones, &target_worlds)?;
+    let facing = target_frame * source_frame.inverse();
+
+    let mut bones = vec![None; target.nodes.len()];
+    for (name, t) in target_bones.iter() {
+        let Some(s) = source_bones.get(name) else {
+            continue;
+        };
+        // the rotation from where the source bone points to where the target bone points, in the rest poses.
+        let tail = tails(name)
+            .into_iter()
+            .find_map(|b| Some((*source_bones.get(&b)?, *target_bones.get(&b)?)));
+        let correction = tail.and_then(|(source_tail, target_tail)| {
+            let from = position(&source_worlds[source_tail]) - position(&source_worlds[*s]);
+            let to = position(&target_worlds[target_tail]) - position(&target_worlds[*t]);
+            UnitQuaternion::rotation_between(&(facing * from), &to)
+        });
+        let correction = correction.unwrap_or_else(UnitQuaternion::identity);
+        bones[*t] = Some(Bone {
+            source: *s,
+            offset: utils::rotation(&source_worlds[*s]).inverse()
+                * facing.inverse()
+                * correction.inverse()
+                * utils::rotation(&target_worlds[*t]),
+        });
+    }
+    let source_hips = *source_bones.get("hips")?;
+    let target_hips = *target_bones.get("hips")?;
+    bones[target_hips].as_ref()?;
+    let times = clip.times();
+    if times.is_empty() {
+        return None;
+    }
+    // the motion of the hips is taken relative to the ground of the source, which is where its origin is, and
+    // starts above the target hips.
+    let source_up = source_frame * Vector3::y();
+    let target_up = target_frame * Vector3::y();
+    let source_height = height(&source_worlds, source_hips, &source_up);
+    let scale = match source_height > 1e-6 {
+        true => height(&target_worlds, target_hips, &target_up) / source_height,
+        false => 1.0,
+    };
+    let horizontal = |p: Point3<f32>, up: &Vector3<f32>| p.coords - up * p.coords.dot(up);
+    let mut source_pose = animation::Pose::new(source);
+    clip.pose(&mut source_pose, times[0]);
+    let start = horizontal(position(&source_pose.world_transforms(source)[source_hips]), &source_up);
+    let ground = horizontal(position(&target_worlds[target_hips]), &target_up);
+
+    let mut parents = vec![None; target.nodes.len()];
+    for (i, node) in target.nodes.iter().enumerate() {
+        for c in node.children.iter() {
+            parents[*c] = Some(i);
+        }
+    }
+    let mut order = Vec::new();
+    let mut stack = target.roots.clone();
+    while let Some(n) = stack.pop() {
+        order.push(n);
+        stack.extend(target.nodes[n].children.iter());
+    }
+
+    let mut rotations = vec![Vec::new(); target.nodes.len()];
+    let mut translations = Vec::new();
+    for time in times.iter() {
+        clip.pose(&mut source_pose, *time);
+        let worlds = source_pose.world_transforms(source);
+        let mut pose = rest.clone();
+        let mut posed = vec![Matrix4::identity(); target.nodes.len()];
+        for n in order.iter().copied() {
+            let parent = parents[n].map_or_else(Matrix4::identity, |p| posed[p]);
+            if let Some(bone) = &bones[n] {
+                let world = facing * utils::rotation(&worlds[bone.source]) * bone.offset;
+                pose.rotations[n] = utils::rotation(&parent).inverse() * world;
+                rotations[n].extend(pose.rotations[n].coords.iter());
+            }
+            if n == target_hips {
+                let moved = position(&worlds[source_hips]).coords - start;
+                let world = Point3::from(ground + facing * moved * scale);
+                let local = parent
+                    .try_inverse()
+                    .unwrap_or_else(Matrix4::identity)
+                    .transform_point(&world);
+                pose.translations[n] = local.coords;
+                translations.extend(local.coords.iter());
+            }
+            posed[n] = parent * pose.transform(n);
+        }
+    }
+
+    let channel = |node: usize, path: animation::Path, values: Vec<f32>| animation::Channel {
+        node: node,
+        path: path,
+        interpolation: animation::Interpolation::Linear,
+        times: times.clone(),
+        values: values,
+    };
+    let mut channels = vec![channel(target_hips, animation::Path::Translation, translations)];
+    for (n, values) in rotations.into_iter().enumerate() {
+        if !values.is_empty() {
+            channels.push(channel(n, animation::Path::Rotation, values));
+        }
+    }
+    Some(animation::Clip {
+        name: clip.name.clone(),
+        channels: channels,
+        expressions: clip.expressions.clone(),
+        look_at: clip.look_at.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a skeleton standing on the origin with legs of length "legs" and the left arm lowered by "arm_angle".
+    fn skeleton(names: [&str; 9], legs: f32, arm_angle: f32) -> scene::Glb {
+        let (s, c) = arm_angle.sin_cos();
+        let joints = [
+            (None, Vector3::new(0.0, legs, 0.0)),
+            (Some(0), Vector3::new(0.0, 0.3, 0.0)),
+            (Some(1), Vector3::new(0.0, 0.3, 0.0)),
+            (Some(0), Vector3::new(0.1, 0.0, 0.0)),
+            (Some(3), Vector3::new(0.0, -legs, 0.0)),
+            (Some(0), Vector3::new(-0.1, 0.0, 0.0)),
+            (Some(5), Vector3::new(0.0, -legs, 0.0)),
+            (Some(1), Vector3::new(0.2, 0.2, 0.0)),
+            (Some(7), Vector3::new(0.3 * c, -0.3 * s, 0.0)),
+        ];
+        let mut nodes = names
+            .iter()
+            .zip(joints.iter())
+            .map(|(name, (_, offset))| scene::Node {
+                name: name.to_string(),
+                translation: *offset,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        for (i, (parent, _)) in joints.iter().enumerate() {
+            if let Some(p) = parent {
+                nodes[*p].children.push(i);
+            }
+        }
+        scene::Glb {
+            nodes: nodes,
+            roots: vec![0],
+            ..Default::default()
+        }
+    }
+
+    const VRM: [&str; 9] = [
+        "hips",
+        "spine",
+        "head",
+        "leftUpperLeg",
+        "leftFoot",
+        "rightUpperLeg",
+        "rightFoot",
+        "leftUpperArm",
+        "leftLowerArm",
+    ];
+
+    #[test]
+    fn guesses_humanoid_bones_from_names() {
+        let names = [
+            "mixamorig:Hips",
+            "Spine",
+            "Head",
+            "LeftUpLeg",
+            "LeftFoot",
+            "upperleg_r",
+            "R_Foot",
+            "mixamorig:LeftArm",
+            "LeftForeArm",
+        ];
+        let bones = humanoid(&skeleton(names, 1.0, 0.0));
+        for (i, bone) in VRM.iter().enumerate() {
+            assert_eq!(bones.get(*bone), Some(&i), "{}", bone);
+        }
+    }
+
+    #[test]
+    fn keeps_the_pose_and_scales_the_hips() {
+        // an A-pose with long legs onto a T-pose.
+        let mut source = skeleton(VRM.map(|b| b), 2.0, f32::consts::FRAC_PI_4);
+        source.humanoid = VRM.iter().enumerate().map(|(i, b)| (b.to_string(), i)).collect();
+        let mut target = skeleton(VRM, 1.0, 0.0);
+        target.humanoid = source.humanoid.clone();
+        let clip = animation::Clip {
+            name: String::new(),
+            channels: vec![animation::Channel {
+                node: 0,
+                path: animation::Path::Translation,
+                interpolation: animation::Interpolation::Linear,
+                times: vec![0.0, 1.0],
+                values: vec![0.0, 2.0, 0.0, 1.0, 2.0, 0.0],
+            }],
+            expressions: Vec::new(),
+            look_at: None,
+        };
+        let rest = animation::Pose::new(&target);
+        target
+            .animations
+            .push(retarget(&source, &clip, &target, &rest).unwrap());
+        let mut player = animation::Player::new(0);
+        player.update(&mut target, &rest, 1.0 - 1e-3);
+
+        let worlds = target.world_transforms();
+        let hips = position(&worlds[0]);
+        assert!((hips - Point3::new(0.5, 1.0, 0.0)).norm() < 1e-2, "{}", hips);
+        let arm = (position(&worlds[8]) - position(&worlds[7])).normalize();
+        let down = Vector3::new(1.0, -1.0, 0.0).normalize();
+        assert!((arm - down).norm() < 1e-4, "{}", arm);
+    }
+}
@@ -1,13 +1,31 @@
+use crate::*;
 use nalgebra::{Matrix4, UnitQuaternion, Vector3};
-use std::*;
+
+#[derive(Debug)]
+pub struct View {
+    pub offset: usize,
+    pub length: usize,
+    pub stride: Option<usize>,
+    pub target: Option<usize>,
+}
 
 #[derive(Debug)]
 pub struct Accessor {
+    pub view: usize,
+    // relative to the view.
     pub offset: usize,
     pub count: usize,
     pub stride: Option<usize>,
     pub component_type: usize,
     pub component_count: usize,
+    pub min: Vec<f32>,
+    pub max: Vec<f32>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
 }
 
 #[derive(Debug)]
@@ -18,8 +36,16 @@ pub struct Attributes {
     pub texcoord_0: Option<usize>,
     pub texcoord_1: Option<usize>,
     //pub color_0: Option<usize>,
-    //pub joints_0: Option<usize>,
-    //pub weights_0: Option<usize>,
+    pub joints_0: Option<usize>,
+    pub weights_0: Option<usize>,
+}
+
+// KHR_texture_transform. it is applied as translation * rotation * scale.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextureTransform {
+    pub offset: [f32; 2],
+    pub rotation: f32,
+    pub scale: [f32; 2],
 }
 
 #[derive(Debug)]
@@ -27,13 +53,26 @@ pub struct Texture {
     pub wrap_s: bool,
     pub wrap_t: bool,
     pub texcoord: usize,
-    pub image: usize,
+    pub transform: TextureTransform,
+    // the glTF texture, whose images are in Glb::textures.
+    pub index: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorSpace {
+    Srgb,
+    Linear,
 }
 
 #[derive(Debug)]
 pub struct Material {
     pub base_color_factor: [f32; 4],
     pub base_color_texture: Option<Texture>,
+    pub emissive_factor: [f32; 3],
+    pub emissive_texture: Option<Texture>,
+    pub metallic_roughness_texture: Option<Texture>,
+    pub normal_texture: Option<Texture>,
+    pub occlusion_texture: Option<Texture>,
 }
 
 #[derive(Debug)]
@@ -42,6 +81,8 @@ pub struct Primitive {
     pub targets: Vec<Attributes>,
     pub indices: Option<usize>,
     pub material: Option<usize>,
+    // the triangles which are not skinned to the head, drawn in the first person view.
+    pub head_less_indices: Option<Vec<u32>>,
 }
 
 #[derive(Debug)]
@@ -50,6 +91,22 @@ pub struct Mesh {
     pub weights: Option<Vec<f32>>,
 }
 
+#[derive(Debug)]
+pub struct Skin {
+    pub joints: Vec<usize>,
+    pub inverse_bind_matrices: Vec<Matrix4<f32>>,
+}
+
+// VRM firstPerson. "Auto" is only kept for the skinned meshes, which are drawn without the head in the first
+// person view.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FirstPerson {
+    Auto,
+    Both,
+    ThirdPersonOnly,
+    FirstPersonOnly,
+}
+
 #[derive(Debug)]
 pub enum Element {
     None,
@@ -64,22 +121,146 @@ pub struct Node {
     pub rotation: UnitQuaternion<f32>,
     pub scale: Vector3<f32>,
     pub element: Element,
+    pub skin: Option<usize>,
+    pub first_person: FirstPerson,
+}
+
+// the BIN chunk, backed by a file mapping or an owned buffer. it is shared with the threads decoding images
+// without being copied.
+#[derive(Clone)]
+pub struct Blob {
+    data: sync::Arc<dyn AsRef<[u8]> + Send + Sync>,
+    range: ops::Range<usize>,
+}
+
+// an encoded image in the blob, decoded after the geometry has been shown.
+#[derive(Clone, Debug)]
+pub struct ImageSource {
+    pub range: ops::Range<usize>,
+    pub mime_type: String,
 }
 
 #[derive(Debug)]
 pub struct Image {
     pub dims: [u32; 3],
     pub buffer: Vec<u8>,
+    pub format: wgpu::TextureFormat,
+    // prebuilt levels after the first one, in the same format.
+    pub mips: Vec<Vec<u8>>,
 }
 
 pub struct Glb {
     pub materials: Vec<Material>,
+    pub views: Vec<View>,
     pub accessors: Vec<Accessor>,
     pub meshes: Vec<Mesh>,
     pub nodes: Vec<Node>,
     pub roots: Vec<usize>,
-    pub blob: Vec<u8>,
-    pub images: Vec<Option<Image>>,
+    pub skins: Vec<Skin>,
+    pub springs: Vec<spring::Spring>,
+    pub expressions: expression::Expressions,
+    // the VRM humanoid bones by their VRM 1.0 names.
+    pub humanoid: collections::HashMap<String, usize>,
+    pub look_at: Option<look_at::LookAt>,
+    pub animations: Vec<animation::Clip>,
+    // released once the GPU has its copy. anything the CPU needs later has to be extracted by the loader.
+    pub blob: Option<Blob>,
+    pub images: Vec<Option<ImageSource>>,
+    // the images of each glTF texture, tried in order until one can be decoded.
+    pub textures: Vec<Vec<usize>>,
+    // the vertices for picking per mesh and primitive, None without positions.
+    pub geometry: Vec<Vec<Option<pick::Geometry>>>,
+}
+
+impl default::Default for TextureTransform {
+    fn default() -> Self {
+        Self {
+            offset: [0.0, 0.0],
+            rotation: 0.0,
+            scale: [1.0, 1.0],
+        }
+    }
+}
+
+impl TextureTransform {
+    // the upper two rows of the 3x3 matrix.
+    pub fn rows(&self) -> [[f32; 4]; 2] {
+        let (s, c) = self.rotation.sin_cos();
+        let [sx, sy] = self.scale;
+        let [ox, oy] = self.offset;
+        [[c * sx, s * sy, ox, 0.0], [-s * sx, c * sy, oy, 0.0]]
+    }
+}
+
+impl Material {
+    // the color space is given by the slot, so an image shared by several slots may be sampled in both.
+    pub fn textures(&self) -> impl Iterator<Item = (&Texture, ColorSpace)> {
+        [
+            (&self.base_color_texture, ColorSpace::Srgb),
+            (&self.emissive_texture, ColorSpace::Srgb),
+            (&self.metallic_roughness_texture, ColorSpace::Linear),
+            (&self.normal_texture, ColorSpace::Linear),
+            (&self.occlusion_texture, ColorSpace::Linear),
+        ]
+        .into_iter()
+        .filter_map(|(texture, color_space)| Some((texture.as_ref()?, color_space)))
+    }
+
+    // the slots the shader samples. the images of the other slots are not decoded until the shading uses them.
+    pub fn sampled_textures(&self) -> impl Iterator<Item = &Texture> {
+        [&self.base_color_texture, &self.emissive_texture].into_iter().flatten()
+    }
+}
+
+impl Image {
+    pub fn rgba8(width: u32, height: u32, buffer: Vec<u8>) -> Self {
+        Image {
+            dims: [width, height, 4],
+            buffer: buffer,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            mips: Vec::new(),
+        }
+    }
+}
+
+impl Blob {
+    pub fn new(data: sync::Arc<dyn AsRef<[u8]> + Send + Sync>, range: ops::Range<usize>) -> Self {
+        Blob {
+            data: data,
+            range: range,
+        }
+    }
+}
+
+impl ops::Deref for Blob {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &(*self.data).as_ref()[self.range.clone()]
+    }
+}
+
+impl default::Default for Glb {
+    fn default() -> Self {
+        Self {
+            materials: Vec::new(),
+            views: Vec::new(),
+            accessors: Vec::new(),
+            meshes: Vec::new(),
+            nodes: Vec::new(),
+            roots: Vec::new(),
+            skins: Vec::new(),
+            springs: Vec::new(),
+            expressions: expression::Expressions::new(Vec::new(), &[], &[]),
+            humanoid: collections::HashMap::new(),
+            look_at: None,
+            animations: Vec::new(),
+            blob: None,
+            images: Vec::new(),
+            textures: Vec::new(),
+            geometry: Vec::new(),
+        }
+    }
 }
 
 impl default::Default for Node {
@@ -91,6 +272,8 @@ impl default::Default for Node {
             rotation: UnitQuaternion::identity(),
             scale: Vector3::new(1.0, 1.0, 1.0),
             element: Element::None,
+            skin: None,
+            first_person: FirstPerson::Both,
         }
     }
 }
@@ -103,3 +286,127 @@ impl Node {
         mt * mr * ms
     }
 }
+
+impl Aabb {
+    pub fn transform(&self, m: &Matrix4<f32>) -> Aabb {
+        let mut dst = Aabb {
+            min: Vector3::repeat(f32::INFINITY),
+            max: Vector3::repeat(f32::NEG_INFINITY),
+        };
+        for i in 0..8 {
+            let v = Vector3::new(
+                if i & 1 == 0 { self.min[0] } else { self.max[0] },
+                if i & 2 == 0 { self.min[1] } else { self.max[1] },
+                if i & 4 == 0 { self.min[2] } else { self.max[2] },
+            );
+            let v = m.transform_point(&v.into());
+            dst.min = dst.min.inf(&v.coords);
+            dst.max = dst.max.sup(&v.coords);
+        }
+        dst
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max),
+        }
+    }
+}
+
+impl Accessor {
+    fn component_size(&self) -> usize {
+        match self.component_type {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            _ => 4,
+        }
+    }
+
+    pub fn byte_length(&self) -> usize {
+        let size = self.component_count * self.component_size();
+        match self.count {
+            0 => 0,
+            n => self.stride.unwrap_or(size) * (n - 1) + size,
+        }
+    }
+
+    // reads the elements into a flat array. integer components are normalized to [0, 1] or [-1, 1] if
+    // "normalized" is set, and converted as they are otherwise.
+    pub fn read(&self, view: &View, blob: &[u8], normalized: bool) -> Vec<f32> {
+        let component_size = self.component_size();
+        let stride = self.stride.unwrap_or(self.component_count * component_size);
+        let data = &blob[view.offset + self.offset..view.offset + self.offset + self.byte_length()];
+        let mut dst = Vec::with_capacity(self.count * self.component_count);
+        for i in 0..self.count {
+            for j in 0..self.component_count {
+                let offset = i * stride + j * component_size;
+                let c = &data[offset..offset + component_size];
+                let (x, scale) = match self.component_type {
+                    5120 => (c[0] as i8 as f32, 127.0),
+                    5121 => (c[0] as f32, 255.0),
+                    5122 => (i16::from_le_bytes([c[0], c[1]]) as f32, 32767.0),
+                    5123 => (u16::from_le_bytes([c[0], c[1]]) as f32, 65535.0),
+                    5125 => (u32::from_le_bytes([c[0], c[1], c[2], c[3]]) as f32, 1.0),
+                    _ => (f32::from_le_bytes([c[0], c[1], c[2], c[3]]), 1.0),
+                };
+                dst.push(if normalized { f32::max(x / scale, -1.0) } else { x });
+            }
+        }
+        dst
+    }
+}
+
+impl Glb {
+    pub fn bounds(&self) -> Option<Aabb> {
+        let mut dst = None;
+        let mut stack = self.roots.iter().map(|n| (*n, Matrix4::identity())).collect::<Vec<_>>();
+        while let Some((n, transform)) = stack.pop() {
+            let node = &self.nodes[n];
+            let transform = transform * node.transform();
+            if let Element::Mesh(mesh) = node.element {
+                for primitive in self.meshes[mesh].primitives.iter() {
+                    if let Some(aabb) = self.primitive_bounds(primitive) {
+                        let aabb = aabb.transform(&transform);
+                        dst = Some(dst.map_or(aabb, |dst: Aabb| dst.union(&aabb)));
+                    }
+                }
+            }
+            stack.extend(node.children.iter().map(|c| (*c, transform)));
+        }
+        dst
+    }
+
+    // writes the expression weights into the meshes and materials. returns the materials to be uploaded again.
+    pub fn apply_expressions(&mut self) -> Vec<usize> {
+        self.expressions.apply(&mut self.meshes, &mut self.materials)
+    }
+
+    // the transforms of all the nodes relative to the scene.
+    pub fn world_transforms(&self) -> Vec<Matrix4<f32>> {
+        let locals = self.nodes.iter().map(|n| n.transform()).collect::<Vec<_>>();
+        self.compose(&locals)
+    }
+
+    // the transforms relative to the scene for the given local transforms of the nodes.
+    pub fn compose(&self, locals: &[Matrix4<f32>]) -> Vec<Matrix4<f32>> {
+        let mut dst = vec![Matrix4::identity(); self.nodes.len()];
+        let mut stack = self.roots.iter().map(|n| (*n, Matrix4::identity())).collect::<Vec<_>>();
+        while let Some((n, transform)) = stack.pop() {
+            dst[n] = transform * locals[n];
+            stack.extend(self.nodes[n].children.iter().map(|c| (*c, dst[n])));
+        }
+        dst
+    }
+
+    pub fn primitive_bounds(&self, primitive: &Primitive) -> Option<Aabb> {
+        let accessor = &self.accessors[primitive.attributes.position?];
+        if accessor.min.len() != 3 || accessor.max.len() != 3 {
+            return None;
+        }
+        Some(Aabb {
+            min: Vector3::from_column_slice(&accessor.min),
+            max: Vector3::from_column_slice(&accessor.max),
+        })
+    }
+}
@@ -1,12 +1,28 @@
 struct Immediate {
-	m_position: mat4x3<f32>,
-	m_normal: mat3x3<f32>,
 	projection_scale: vec4<f32>,
 }
 
+struct Instance {
+	@location(4) m_position_0: vec4<f32>,
+	@location(5) m_position_1: vec4<f32>,
+	@location(6) m_position_2: vec4<f32>,
+	@location(7) m_position_3: vec4<f32>,
+	@location(8) m_normal_0: vec4<f32>,
+	@location(9) m_normal_1: vec4<f32>,
+	@location(10) m_normal_2: vec4<f32>,
+	@location(11) joint_offset: u32,
+	// the offset of the weights, the number of targets, the offset of the deltas and the number of vertices.
+	@location(14) morph: vec4<u32>,
+	@location(15) selected: u32,
+}
+
 struct Material {
 	base_color_factor: vec4<f32>,
+	emissive_factor: vec4<f32>,
+	base_color_transform: array<vec4<f32>, 2>,
+	emissive_transform: array<vec4<f32>, 2>,
 	base_color_texcoord: u32,
+	emissive_texcoord: u32,
 }
 
 struct VertexToFragment {
@@ -15,24 +31,64 @@ struct VertexToFragment {
 	@location(1) normal: vec3<f32>,
 	@location(2) @interpolate(perspective, sample) texcoord_0: vec2<f32>,
 	@location(3) @interpolate(perspective, sample) texcoord_1: vec2<f32>,
+	@location(4) @interpolate(flat) selected: u32,
 }
 
-var<immediate> imm: Immediate;
+// "imm" is declared by the renderer, as immediates or as a uniform buffer at group 1.
 @group(0) @binding(0) var<uniform> material: Material;
 @group(0) @binding(1) var base_color_texture: texture_2d<f32>;
 @group(0) @binding(2) var base_color_sampler: sampler;
+@group(0) @binding(3) var emissive_texture: texture_2d<f32>;
+@group(2) @binding(0) var<storage, read> joints: array<mat4x4<f32>>;
+@group(2) @binding(1) var<storage, read> morph_weights: array<f32>;
+@group(3) @binding(0) var<storage, read> morph_deltas: array<vec4<f32>>;
+
+fn transform_texcoord(rows: array<vec4<f32>, 2>, texcoord: vec2<f32>) -> vec2<f32> {
+	return vec2(dot(rows[0].xyz, vec3(texcoord, 1.0)), dot(rows[1].xyz, vec3(texcoord, 1.0)));
+}
 
 @vertex fn vs_main(
+	@builtin(vertex_index) vertex_index: u32,
 	@location(0) position: vec3<f32>,
 	@location(1) normal: vec3<f32>,
 	@location(2) texcoord_0: vec2<f32>,
-	@location(3) texcoord_1: vec2<f32>
+	@location(3) texcoord_1: vec2<f32>,
+	@location(12) joint_indices: vec4<u32>,
+	@location(13) joint_weights: vec4<f32>,
+	instance: Instance,
 ) -> VertexToFragment {
+	let m_position = mat4x4(instance.m_position_0, instance.m_position_1, instance.m_position_2, instance.m_position_3);
+	let m_normal = mat3x3(instance.m_normal_0.xyz, instance.m_normal_1.xyz, instance.m_normal_2.xyz);
+	var morphed_position = position;
+	var morphed_normal = normal;
+	for (var i = 0u; i < instance.morph.y; i++) {
+		let weight = morph_weights[instance.morph.x + i];
+		if (weight != 0.0) {
+			let delta = instance.morph.z + (i * instance.morph.w + vertex_index) * 2u;
+			morphed_position += weight * morph_deltas[delta].xyz;
+			morphed_normal += weight * morph_deltas[delta + 1u].xyz;
+		}
+	}
+	// the vertices without a skin have zero weights.
+	var m_skin = mat4x4(
+		vec4(1.0, 0.0, 0.0, 0.0),
+		vec4(0.0, 1.0, 0.0, 0.0),
+		vec4(0.0, 0.0, 1.0, 0.0),
+		vec4(0.0, 0.0, 0.0, 1.0),
+	);
+	if (dot(joint_weights, vec4(1.0)) > 0.0) {
+		let offset = instance.joint_offset;
+		m_skin = joint_weights.x * joints[offset + joint_indices.x] +
+			joint_weights.y * joints[offset + joint_indices.y] +
+			joint_weights.z * joints[offset + joint_indices.z] +
+			joint_weights.w * joints[offset + joint_indices.w];
+	}
 	var vtf: VertexToFragment;
-	vtf.position = imm.m_position * vec4(position, 1.0);
-	vtf.normal = imm.m_normal * normal;
+	vtf.position = (m_position * m_skin * vec4(morphed_position, 1.0)).xyz;
+	vtf.normal = m_normal * (m_skin * vec4(morphed_normal, 0.0)).xyz;
 	vtf.texcoord_0 = texcoord_0;
 	vtf.texcoord_1 = texcoord_1;
+	vtf.selected = instance.selected;
 	vtf.builtin_position = (imm.projection_scale * vec4(vtf.position, 1.0)).xywz;
 	return vtf;
 }
@@ -40,7 +96,24 @@ var<immediate> imm: Immediate;
 @fragment fn fs_main(vtf: VertexToFragment, @builtin(sample_index) sample_index: u32) -> @location(0) vec4<f32> {
 	let base_color = material.base_color_factor * textureSample(
 		base_color_texture, base_color_sampler,
-		select(vtf.texcoord_0, vtf.texcoord_1, material.base_color_texcoord > 0)
+		transform_texcoord(
+			material.base_color_transform,
+			select(vtf.texcoord_0, vtf.texcoord_1, material.base_color_texcoord > 0)
+		)
 	);
-	return base_color;
+	let emissive = material.emissive_factor.rgb * textureSample(
+		emissive_texture, base_color_sampler,
+		transform_texcoord(
+			material.emissive_transform,
+			select(vtf.texcoord_0, vtf.texcoord_1, material.emissive_texcoord > 0)
+		)
+	).rgb;
+	let color = base_color.rgb + emissive;
+	// the picked node is tinted.
+	return vec4(select(color, mix(color, vec3(1.0, 0.4, 0.0), 0.4), vtf.selected != 0u), base_color.a);
+}
+
+// the pixels of the selected nodes, which get an outline.
+@fragment fn fs_mask() -> @location(0) vec4<f32> {
+	return vec4(1.0);
 }
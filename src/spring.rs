@@ -0,0 +1,326 @@
+use crate::*;
+use nalgebra::{Matrix4, Point3, UnitQuaternion, Vector3};
+
+#[derive(Clone, Debug)]
+pub enum Shape {
+    Sphere {
+        offset: Vector3<f32>,
+        radius: f32,
+    },
+    Capsule {
+        offset: Vector3<f32>,
+        radius: f32,
+        tail: Vector3<f32>,
+    },
+}
+
+// the offsets are in the space of the node.
+#[derive(Clone, Debug)]
+pub struct Collider {
+    pub node: usize,
+    pub shape: Shape,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Tail {
+    Node(usize),
+    // a virtual tail beyond a leaf node, continuing the direction from its parent.
+    Extend(f32),
+}
+
+#[derive(Clone, Debug)]
+pub struct Joint {
+    pub node: usize,
+    pub tail: Tail,
+    pub hit_radius: f32,
+    pub stiffness: f32,
+    pub gravity_power: f32,
+    pub gravity_dir: Vector3<f32>,
+    pub drag_force: f32,
+}
+
+// the joints are ordered from the root of the chain, parents before their children.
+#[derive(Clone, Debug)]
+pub struct Spring {
+    pub joints: Vec<Joint>,
+    pub colliders: Vec<Collider>,
+    // the node whose motion is not passed on to the joints.
+    pub center: Option<usize>,
+}
+
+struct State {
+    bone_axis: Vector3<f32>,
+    length: f32,
+    initial_rotation: UnitQuaternion<f32>,
+    // in the space of the center.
+    prev_tail: Point3<f32>,
+    current_tail: Point3<f32>,
+}
+
+// steps the springs at a fixed rate, so that the motion does not depend on the frame rate.
+pub struct Simulation {
+    parents: Vec<Option<usize>>,
+    states: Vec<Vec<State>>,
+    accumulator: f32,
+}
+
+fn position(m: &Matrix4<f32>) -> Point3<f32> {
+    m.transform_point(&Point3::origin())
+}
+
+fn closest_on_segment(p: &Point3<f32>, a: &Point3<f32>, b: &Point3<f32>) -> Point3<f32> {
+    let ab = b - a;
+    let t = match ab.norm_squared() {
+        0.0 => 0.0,
+        n => ((p - a).dot(&ab) / n).clamp(0.0, 1.0),
+    };
+    a + ab * t
+}
+
+impl Simulation {
+    pub const STEP: f32 = 1.0 / 60.0;
+    // a longer frame is simulated as if it took this long, instead of catching up with many steps.
+    pub const MAX_DT: f32 = 0.1;
+
+    pub fn new(glb: &scene::Glb, placement: &Matrix4<f32>) -> Option<Self> {
+        if glb.springs.is_empty() {
+            return None;
+        }
+        let mut parents = vec![None; glb.nodes.len()];
+        for (i, node) in glb.nodes.iter().enumerate() {
+            for c in node.children.iter() {
+                parents[*c] = Some(i);
+            }
+        }
+        let worlds = glb
+            .world_transforms()
+            .into_iter()
+            .map(|m| placement * m)
+            .collect::<Vec<_>>();
+        let states = glb
+            .springs
+            .iter()
+            .map(|spring| {
+                let center = spring.center.map_or_else(Matrix4::identity, |c| worlds[c]);
+                let center_inv = center.try_inverse().unwrap_or_else(Matrix4::identity);
+                spring
+                    .joints
+                    .iter()
+                    .map(|joint| {
+                        let node = &glb.nodes[joint.node];
+                        let world = &worlds[joint.node];
+                        let tail = match joint.tail {
+                            Tail::Node(n) => position(&worlds[n]),
+                            Tail::Extend(length) => {
+                                let from = parents[joint.node].map_or_else(Point3::origin, |p| position(&worlds[p]));
+                                let dir = position(world) - from;
+                                let dir = match dir.norm() {
+                                    0.0 => Vector3::y(),
+                                    n => dir / n,
+                                };
+                                position(world) + dir * length
+                            }
+                        };
+                        let local_tail = world
+                            .try_inverse()
+                            .unwrap_or_else(Matrix4::identity)
+                            .transform_point(&tail);
+                        let axis = local_tail.coords.component_mul(&node.scale);
+                        let tail = center_inv.transform_point(&tail);
+                        State {
+                            bone_axis: axis.try_normalize(0.0).unwrap_or_else(Vector3::y),
+                            length: (position(world) - center.transform_point(&tail)).norm(),
+                            initial_rotation: node.rotation,
+                            prev_tail: tail,
+                            current_tail: tail,
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        Some(Simulation {
+            parents: parents,
+            states: states,
+            accumulator: 0.0,
+        })
+    }
+
+    // advances the time by dt and writes the rotations of the joints into the nodes.
+    pub fn update(&mut self, glb: &mut scene::Glb, placement: &Matrix4<f32>, dt: f32) {
+        self.accumulator += dt.clamp(0.0, Self::MAX_DT);
+        while self.accumulator >= Self::STEP {
+            self.step(glb, placement);
+            self.accumulator -= Self::STEP;
+        }
+    }
+
+    fn step(&mut self, glb: &mut scene::Glb, placement: &Matrix4<f32>) {
+        let mut worlds = glb
+            .world_transforms()
+            .into_iter()
+            .map(|m| placement * m)
+            .collect::<Vec<_>>();
+        let dt = Self::STEP;
+        for (spring, states) in glb.springs.iter().zip(self.states.iter_mut()) {
+            let center = spring.center.map_or_else(Matrix4::identity, |c| worlds[c]);
+            let center_inv = center.try_inverse().unwrap_or_else(Matrix4::identity);
+            for (joint, state) in spring.joints.iter().zip(states.iter_mut()) {
+                let head = position(&worlds[joint.node]);
+                let parent_rotation = self.parents[joint.node]
+                    .map_or_else(|| utils::rotation(placement), |p| utils::rotation(&worlds[p]));
+                let current = center.transform_point(&state.current_tail);
+                let prev = center.transform_point(&state.prev_tail);
+
+                let inertia = (current - prev) * (1.0 - joint.drag_force);
+                let stiffness = parent_rotation * state.initial_rotation * state.bone_axis * (dt * joint.stiffness);
+                let gravity = joint.gravity_dir * (dt * joint.gravity_power);
+                let next = current + inertia + stiffness + gravity;
+                let constrain =
+                    |p: Point3<f32>| head + (p - head).try_normalize(0.0).unwrap_or(state.bone_axis) * state.length;
+                let mut next = constrain(next);
+
+                for collider in spring.colliders.iter() {
+                    let m = &worlds[collider.node];
+                    let (closest, radius) = match &collider.shape {
+                        Shape::Sphere { offset, radius } => (m.transform_point(&Point3::from(*offset)), *radius),
+                        Shape::Capsule { offset, radius, tail } => {
+                            let a = m.transform_point(&Point3::from(*offset));
+                            let b = m.transform_point(&Point3::from(*tail));
+                            (closest_on_segment(&next, &a, &b), *radius)
+                        }
+                    };
+                    let d = next - closest;
+                    let r = radius + joint.hit_radius;
+                    if d.norm_squared() < r * r {
+                        next = constrain(closest + d.try_normalize(0.0).unwrap_or_else(Vector3::y) * r);
+                    }
+                }
+
+                state.prev_tail = state.current_tail;
+                state.current_tail = center_inv.transform_point(&next);
+
+                let to = (parent_rotation * state.initial_rotation).inverse() * (next - head);
+                let delta =
+                    UnitQuaternion::rotation_between(&state.bone_axis, &to).unwrap_or_else(UnitQuaternion::identity);
+                glb.nodes[joint.node].rotation = state.initial_rotation * delta;
+                Self::update_subtree(&glb.nodes, &mut worlds, &self.parents, placement, joint.node);
+            }
+        }
+    }
+
+    fn update_subtree(
+        nodes: &[scene::Node],
+        worlds: &mut [Matrix4<f32>],
+        parents: &[Option<usize>],
+        placement: &Matrix4<f32>,
+        root: usize,
+    ) {
+        let mut stack = vec![root];
+        while let Some(n) = stack.pop() {
+            let parent = parents[n].map_or(*placement, |p| worlds[p]);
+            worlds[n] = parent * nodes[n].transform();
+            stack.extend(nodes[n].children.iter());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a horizontal chain of "count" bones of length 1 along +x, hanging from node 0.
+    fn chain(count: usize, gravity_power: f32, colliders: Vec<Collider>) -> scene::Glb {
+        let mut nodes = (0..=count)
+            .map(|i| scene::Node {
+                translation: if i == 0 { Vector3::zeros() } else { Vector3::x() },
+                children: if i < count { vec![i + 1] } else { Vec::new() },
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        nodes.push(Default::default());
+        let joints = (0..count)
+            .map(|i| Joint {
+                node: i,
+                tail: Tail::Node(i + 1),
+                hit_radius: 0.0,
+                stiffness: 0.0,
+                gravity_power: gravity_power,
+                gravity_dir: -Vector3::y(),
+                drag_force: 0.4,
+            })
+            .collect();
+        scene::Glb {
+            nodes: nodes,
+            roots: vec![0, count + 1],
+            springs: vec![Spring {
+                joints: joints,
+                colliders: colliders,
+                center: None,
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn run(glb: &mut scene::Glb, dts: &[f32]) {
+        let mut simulation = Simulation::new(glb, &Matrix4::identity()).unwrap();
+        for dt in dts {
+            simulation.update(glb, &Matrix4::identity(), *dt);
+        }
+    }
+
+    #[test]
+    fn keeps_bone_lengths() {
+        let mut glb = chain(3, 2.0, Vec::new());
+        run(&mut glb, &[0.05; 40]);
+        let worlds = glb.world_transforms();
+        for i in 0..3 {
+            let d = position(&worlds[i + 1]) - position(&worlds[i]);
+            assert!((d.norm() - 1.0).abs() < 1e-4, "{}", d.norm());
+        }
+    }
+
+    #[test]
+    fn hangs_under_gravity() {
+        let mut glb = chain(1, 2.0, Vec::new());
+        run(&mut glb, &[0.1; 100]);
+        let tail = position(&glb.world_transforms()[1]);
+        assert!(tail.y < -0.99, "{}", tail);
+    }
+
+    #[test]
+    fn is_deterministic_and_independent_of_frame_rate() {
+        let mut a = chain(3, 1.0, Vec::new());
+        let mut b = chain(3, 1.0, Vec::new());
+        run(&mut a, &[1.0 / 30.0; 30]);
+        run(&mut b, &[1.0 / 60.0; 60]);
+        for (a, b) in a.nodes.iter().zip(b.nodes.iter()) {
+            assert!(a.rotation.angle_to(&b.rotation) < 1e-5);
+        }
+        let mut c = chain(3, 1.0, Vec::new());
+        run(&mut c, &[1.0 / 30.0; 30]);
+        assert!(
+            a.nodes
+                .iter()
+                .zip(c.nodes.iter())
+                .all(|(a, c)| a.rotation == c.rotation)
+        );
+    }
+
+    #[test]
+    fn rests_on_colliders() {
+        // a sphere where the tail would hang without it.
+        let collider = Collider {
+            node: 2,
+            shape: Shape::Sphere {
+                offset: Vector3::new(0.0, -1.0, 0.0),
+                radius: 0.3,
+            },
+        };
+        let mut glb = chain(1, 2.0, vec![collider]);
+        run(&mut glb, &[0.1; 100]);
+        let tail = position(&glb.world_transforms()[1]);
+        let d = (tail - Point3::new(0.0, -1.0, 0.0)).norm();
+        assert!(0.3 - 1e-2 < d && d < 0.35, "{}", tail);
+        assert!(tail.x > 0.0, "{}", tail);
+    }
+}
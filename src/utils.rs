@@ -3,3 +3,37 @@ use std::*;
 pub unsafe fn as_bytes<T>(v: &T) -> &[u8] {
     unsafe { slice::from_raw_parts(v as *const T as *const u8, mem::size_of::<T>()) }
 }
+
+pub unsafe fn slice_as_bytes<T>(v: &[T]) -> &[u8] {
+    unsafe { slice::from_raw_parts(v.as_ptr() as *const u8, mem::size_of_val(v)) }
+}
+
+// the rotation of a transform with its scale removed.
+pub fn rotation(m: &nalgebra::Matrix4<f32>) -> nalgebra::UnitQuaternion<f32> {
+    let m = m.fixed_view::<3, 3>(0, 0);
+    let m = nalgebra::Matrix3::from_columns(&[
+        m.column(0).normalize(),
+        m.column(1).normalize(),
+        m.column(2).normalize(),
+    ]);
+    nalgebra::UnitQuaternion::from_matrix(&m)
+}
+
+// calls f for each item on as many threads as there are cores.
+pub fn par_for_each<T: Sync>(items: &[T], f: impl Fn(usize, &T) + Sync) {
+    let next = sync::atomic::AtomicUsize::new(0);
+    let n = thread::available_parallelism().map_or(1, |n| n.get()).min(items.len());
+    thread::scope(|scope| {
+        for _ in 0..n {
+            scope.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, sync::atomic::Ordering::Relaxed);
+                    let Some(item) = items.get(i) else {
+                        break;
+                    };
+                    f(i, item);
+                }
+            });
+        }
+    });
+}
@@ -0,0 +1,79 @@
+use std::*;
+
+struct Entry {
+    path: String,
+    reported: Option<time::SystemTime>,
+    pending: Option<time::SystemTime>,
+}
+
+// polls the modification times. a change is reported once the time stays the same for two polls, so that a
+// file which is still being written by an exporter is not picked up halfway.
+pub struct Watcher {
+    entries: Vec<Entry>,
+}
+
+fn modified(path: &str) -> Option<time::SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl Watcher {
+    pub const INTERVAL: time::Duration = time::Duration::from_millis(250);
+
+    pub fn new(paths: impl IntoIterator<Item = String>) -> Self {
+        let entries = paths
+            .into_iter()
+            .map(|path| {
+                let t = modified(&path);
+                Entry {
+                    path: path,
+                    reported: t,
+                    pending: t,
+                }
+            })
+            .collect();
+        Watcher { entries: entries }
+    }
+
+    // starts watching another file, unless it is watched already.
+    pub fn add(&mut self, path: String) {
+        if self.entries.iter().any(|e| e.path == path) {
+            return;
+        }
+        let t = modified(&path);
+        self.entries.push(Entry {
+            path: path,
+            reported: t,
+            pending: t,
+        });
+    }
+
+    pub fn poll(&mut self) -> Vec<String> {
+        let mut changed = Vec::new();
+        for entry in self.entries.iter_mut() {
+            let t = modified(&entry.path);
+            if t == entry.reported {
+                entry.pending = t;
+            } else if t == entry.pending {
+                entry.reported = t;
+                changed.push(entry.path.clone());
+            } else {
+                entry.pending = t;
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_files_once() {
+        let mut watcher = Watcher::new(["a.glb".to_string()]);
+        watcher.add("b.glb".to_string());
+        watcher.add("a.glb".to_string());
+        let paths = watcher.entries.iter().map(|e| e.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths, ["a.glb", "b.glb"]);
+    }
+}
@@ -0,0 +1,261 @@
+use crate::*;
+use nalgebra::{Point3, UnitQuaternion, Vector3};
+
+pub struct Model {
+    pub id: usize,
+    pub path: String,
+    pub glb: scene::Glb,
+    pub placement: scene::Node,
+    pub springs: Option<spring::Simulation>,
+    pub gaze: Option<look_at::Controller>,
+    // the pose as loaded, which the clips are played and retargeted against.
+    pub rest: animation::Pose,
+    pub player: Option<animation::Player>,
+}
+
+pub struct World {
+    pub models: Vec<Model>,
+    // the files without meshes, retargeted onto every model including the ones loaded or reloaded later.
+    motions: Vec<scene::Glb>,
+    next_id: usize,
+}
+
+// appends the clips of a motion which can be retargeted onto the model and plays the first of them. returns whether
+// there were any.
+fn attach(model: &mut Model, source: &scene::Glb) -> bool {
+    let start = model.glb.animations.len();
+    for clip in source.animations.iter() {
+        // clips with only expressions and lookAt need no skeleton.
+        let retargeted = retarget::retarget(source, clip, &model.glb, &model.rest);
+        let unbound = (!clip.expressions.is_empty() || clip.look_at.is_some()).then(|| animation::Clip {
+            name: clip.name.clone(),
+            channels: Vec::new(),
+            expressions: clip.expressions.clone(),
+            look_at: clip.look_at.clone(),
+        });
+        if let Some(clip) = retargeted.or(unbound) {
+            model.glb.animations.push(clip);
+        }
+    }
+    if model.glb.animations.len() == start {
+        return false;
+    }
+    model.player = Some(animation::Player::new(start));
+    true
+}
+
+impl World {
+    pub const SPACING: f32 = 0.25;
+
+    pub fn new() -> Self {
+        World {
+            models: Vec::new(),
+            motions: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    // ids are handed out before loading so that the images decoded afterwards can find their model.
+    pub fn reserve_id(&mut self) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    pub fn add(&mut self, id: usize, path: String, glb: scene::Glb, placement: scene::Node) {
+        let springs = spring::Simulation::new(&glb, &placement.transform());
+        let gaze = look_at::Controller::new(&glb);
+        let rest = animation::Pose::new(&glb);
+        let player = (!glb.animations.is_empty()).then(|| animation::Player::new(0));
+        let mut model = Model {
+            id: id,
+            path: path,
+            glb: glb,
+            placement: placement,
+            springs: springs,
+            gaze: gaze,
+            rest: rest,
+            player: player,
+        };
+        for motion in self.motions.iter() {
+            attach(&mut model, motion);
+        }
+        self.models.push(model);
+    }
+
+    // places the model to the right of the existing ones.
+    pub fn add_side_by_side(&mut self, id: usize, path: String, glb: scene::Glb) {
+        let bounds = glb.bounds();
+        let right = self
+            .models
+            .iter()
+            .filter_map(|m| Some(m.placement.translation[0] + m.glb.bounds()?.max[0]))
+            .reduce(f32::max);
+        let x = match (right, bounds) {
+            (Some(right), Some(bounds)) => right + Self::SPACING - bounds.min[0],
+            _ => 0.0,
+        };
+        let placement = scene::Node {
+            translation: Vector3::new(x, 0.0, 0.0),
+            ..Default::default()
+        };
+        self.add(id, path, glb, placement);
+    }
+
+    // swaps in a reloaded scene. the model gets a new id so that its GPU resources are recreated. the motions are
+    // retargeted again and the player goes on where it was.
+    pub fn replace(&mut self, id: usize, new_id: usize, glb: scene::Glb) -> bool {
+        let Some(model) = self.models.iter_mut().find(|m| m.id == id) else {
+            return false;
+        };
+        model.id = new_id;
+        model.springs = spring::Simulation::new(&glb, &model.placement.transform());
+        model.gaze = look_at::Controller::new(&glb);
+        model.rest = animation::Pose::new(&glb);
+        let player = model.player.take();
+        model.player = (!glb.animations.is_empty()).then(|| animation::Player::new(0));
+        model.glb = glb;
+        for motion in self.motions.iter() {
+            attach(model, motion);
+        }
+        if let (Some(player), Some(last)) = (player, model.glb.animations.len().checked_sub(1)) {
+            model.player = Some(animation::Player {
+                clip: player.clip.min(last),
+                time: player.time,
+                playing: player.playing,
+            });
+        }
+        true
+    }
+
+    // the eyes of the first VRM model, for the first person view. the position is the lookAt origin and the
+    // rotation turns +z to where the model faces.
+    pub fn first_person_eye(&self) -> Option<(Point3<f32>, UnitQuaternion<f32>)> {
+        self.models.iter().find_map(|model| {
+            let head = *model.glb.humanoid.get("head")?;
+            let (offset, facing) = match &model.glb.look_at {
+                Some(look_at) => (look_at.offset, look_at.facing),
+                None => (Vector3::zeros(), UnitQuaternion::identity()),
+            };
+            let transform = model.placement.transform() * model.glb.world_transforms()[head];
+            Some((
+                transform.transform_point(&Point3::from(offset)),
+                model.placement.rotation * facing,
+            ))
+        })
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Model> {
+        self.models.iter().find(|m| m.id == id)
+    }
+
+    // the closest triangle along a ray, with the id of its model.
+    pub fn pick(&self, ray: &pick::Ray, first_person: bool) -> Option<(usize, pick::Hit)> {
+        self.models
+            .iter()
+            .filter_map(|m| Some((m.id, pick::cast(&m.glb, &m.placement.transform(), ray, first_person)?)))
+            .min_by(|a, b| a.1.distance.total_cmp(&b.1.distance))
+    }
+
+    // points the eyes at the target, or straight ahead if there is none. returns the materials to be uploaded again,
+    // as the indices of the model and of the material.
+    pub fn look_at(&mut self, target: Option<&Point3<f32>>) -> Vec<(usize, usize)> {
+        let mut materials = Vec::new();
+        for (i, model) in self.models.iter_mut().enumerate() {
+            // the lookAt track of the clip being played wins over the target.
+            let player = model.player.as_ref();
+            let angles = player.and_then(|p| model.glb.animations.get(p.clip)?.look_at_angles(p.time));
+            if let Some(gaze) = &model.gaze {
+                let changed = match angles {
+                    Some((yaw, pitch)) => gaze.turn(&mut model.glb, yaw, pitch),
+                    None => gaze.update(&mut model.glb, &model.placement.transform(), target),
+                };
+                materials.extend(changed.into_iter().map(|m| (i, m)));
+            }
+        }
+        materials
+    }
+
+    // retargets the clips of a file without meshes onto the humanoid models and plays the first of them. the
+    // models loaded later get them too. returns the number of models which got them now.
+    pub fn add_motion(&mut self, mut source: scene::Glb) -> usize {
+        // the clips have been read already.
+        source.blob = None;
+        let count = self
+            .models
+            .iter_mut()
+            .map(|m| attach(m, &source))
+            .filter(|a| *a)
+            .count();
+        self.motions.push(source);
+        count
+    }
+
+    // plays the clips, puts the humanoid models into the edited pose, if any, and steps the simulations. returns
+    // whether anything is moving, which keeps the window redrawing, and the materials to be uploaded again, as the
+    // indices of the model and of the material.
+    pub fn animate(&mut self, dt: f32, pose: Option<&pose::HumanoidPose>) -> (bool, Vec<(usize, usize)>) {
+        let mut animated = false;
+        let mut materials = Vec::new();
+        for (i, model) in self.models.iter_mut().enumerate() {
+            if let Some(player) = &mut model.player {
+                let changed = player.update(&mut model.glb, &model.rest, dt);
+                materials.extend(changed.into_iter().map(|m| (i, m)));
+                animated |= player.playing;
+            }
+            if let Some(pose) = pose {
+                pose.apply(&mut model.glb, &model.rest);
+            }
+            if let Some(springs) = &mut model.springs {
+                springs.update(&mut model.glb, &model.placement.transform(), dt);
+                animated = true;
+            }
+        }
+        (animated, materials)
+    }
+
+    // the humanoid bones of any of the models, parents first.
+    pub fn humanoid_bones(&self) -> Vec<&'static str> {
+        pose::BONES
+            .into_iter()
+            .filter(|b| self.models.iter().any(|m| m.glb.humanoid.contains_key(*b)))
+            .collect()
+    }
+
+    pub fn remove(&mut self, id: usize) -> Option<Model> {
+        let i = self.models.iter().position(|m| m.id == id)?;
+        Some(self.models.remove(i))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glb(clips: usize) -> scene::Glb {
+        let clip = |i| animation::Clip {
+            name: format!("clip {}", i),
+            channels: Vec::new(),
+            expressions: Vec::new(),
+            look_at: None,
+        };
+        scene::Glb {
+            animations: (0..clips).map(clip).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn replace_keeps_the_player() {
+        let mut world = World::new();
+        world.add(0, "a.glb".to_string(), glb(3), scene::Node::default());
+        world.models[0].player = Some(animation::Player {
+            clip: 2,
+            time: 0.5,
+            playing: false,
+        });
+        assert!(world.replace(0, 1, glb(2)));
+        let player = world.get(1).unwrap().player.as_ref().unwrap();
+        assert_eq!((player.clip, player.time, player.playing), (1, 0.5, false));
+    }
+}
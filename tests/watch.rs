@@ -0,0 +1,30 @@
+// the watcher against a real file. the crate is a binary, so the module is compiled into the test directly.
+#[allow(dead_code)]
+#[path = "../src/watch.rs"]
+mod watch;
+
+use std::*;
+
+#[test]
+fn reports_settled_changes() {
+    let path = env::temp_dir().join(format!("yavv-watch-{}.glb", process::id()));
+    fs::write(&path, b"a").unwrap();
+    let file = fs::File::options().write(true).open(&path).unwrap();
+    let t0 = file.metadata().unwrap().modified().unwrap();
+    let path_str = path.to_str().unwrap().to_string();
+
+    let mut watcher = watch::Watcher::new([path_str.clone()]);
+    assert!(watcher.poll().is_empty());
+
+    file.set_modified(t0 + time::Duration::from_secs(1)).unwrap();
+    assert!(watcher.poll().is_empty());
+    file.set_modified(t0 + time::Duration::from_secs(2)).unwrap();
+    assert!(watcher.poll().is_empty());
+    assert_eq!(watcher.poll(), [path_str.clone()]);
+    assert!(watcher.poll().is_empty());
+
+    fs::remove_file(&path).unwrap();
+    assert!(watcher.poll().is_empty());
+    assert_eq!(watcher.poll(), [path_str.clone()]);
+    assert!(watcher.poll().is_empty());
+}